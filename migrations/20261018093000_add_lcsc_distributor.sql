@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE order_items
+ADD COLUMN lcsc_pn TEXT;
+
+ALTER TABLE order_bom
+ADD COLUMN bom_file_lcsc BYTEA;
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE order_items
+ADD COLUMN tme_pn TEXT;
+
+ALTER TABLE order_bom
+ADD COLUMN bom_file_tme BYTEA;
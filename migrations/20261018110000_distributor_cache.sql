@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS distributor_cache (
+    distributor TEXT NOT NULL,
+    manufacturer TEXT NOT NULL,
+    manufacturer_pn TEXT NOT NULL,
+    quantity_bracket INT NOT NULL,
+    quote TEXT, -- serialized quote, NULL when the part was not found
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (distributor, manufacturer, manufacturer_pn, quantity_bracket)
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE order_items
+ADD COLUMN purchase_quantity INT;
@@ -0,0 +1,24 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS order_item_allocations (
+    order_id INT NOT NULL,
+    manufacturer TEXT NOT NULL,
+    manufacturer_pn TEXT NOT NULL,
+    distributor TEXT NOT NULL,
+    distributor_pn TEXT NOT NULL,
+    quantity INT NOT NULL,
+    unit_price DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (order_id, manufacturer, manufacturer_pn, distributor),
+    FOREIGN KEY (order_id, manufacturer, manufacturer_pn) REFERENCES order_items(order_id, manufacturer, manufacturer_pn) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- carts of already generated BOMs keep working: prices are unknown until the BOM is generated again
+INSERT INTO order_item_allocations (order_id, manufacturer, manufacturer_pn, distributor, distributor_pn, quantity, unit_price)
+SELECT order_id, manufacturer, manufacturer_pn, 'mouser', mouser_pn, COALESCE(purchase_quantity, quantity), 0 FROM order_items WHERE mouser_pn IS NOT NULL
+UNION ALL
+SELECT order_id, manufacturer, manufacturer_pn, 'digikey', digikey_pn, COALESCE(purchase_quantity, quantity), 0 FROM order_items WHERE digikey_pn IS NOT NULL
+UNION ALL
+SELECT order_id, manufacturer, manufacturer_pn, 'farnell', farnell_pn, COALESCE(purchase_quantity, quantity), 0 FROM order_items WHERE farnell_pn IS NOT NULL
+UNION ALL
+SELECT order_id, manufacturer, manufacturer_pn, 'lcsc', lcsc_pn, COALESCE(purchase_quantity, quantity), 0 FROM order_items WHERE lcsc_pn IS NOT NULL
+UNION ALL
+SELECT order_id, manufacturer, manufacturer_pn, 'tme', tme_pn, COALESCE(purchase_quantity, quantity), 0 FROM order_items WHERE tme_pn IS NOT NULL;
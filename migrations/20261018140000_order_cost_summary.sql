@@ -0,0 +1,3 @@
+-- Add migration script here
+-- landed cost of the distributor assignment chosen at the last BOM generation, as JSON
+ALTER TABLE order_bom ADD COLUMN IF NOT EXISTS cost_summary TEXT;
@@ -0,0 +1,25 @@
+-- Add migration script here
+-- every distributor answer seen for each item at the last BOM generation: a quote, or the reason why there is none
+CREATE TABLE IF NOT EXISTS item_sourcing_results (
+    order_id INT NOT NULL,
+    manufacturer TEXT NOT NULL,
+    manufacturer_pn TEXT NOT NULL,
+    distributor TEXT NOT NULL,
+    distributor_pn TEXT,
+    quoted_manufacturer TEXT,
+    quoted_manufacturer_pn TEXT,
+    description TEXT,
+    product_url TEXT,
+    unit_price DOUBLE PRECISION,
+    availability INT,
+    minimum_order_quantity INT,
+    price_breaks TEXT, -- JSON array of {quantity, unit_price}
+    error TEXT,
+    error_message TEXT,
+    searched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (order_id, manufacturer, manufacturer_pn, distributor),
+    FOREIGN KEY (order_id, manufacturer, manufacturer_pn) REFERENCES order_items(order_id, manufacturer, manufacturer_pn) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- notes about the item as a whole (why it was moved, why it could not be sourced), written in every BOM row of the item
+ALTER TABLE order_items ADD COLUMN IF NOT EXISTS sourcing_notes TEXT;
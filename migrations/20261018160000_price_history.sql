@@ -0,0 +1,16 @@
+-- Add migration script here
+-- price and stock seen at every live distributor search, never overwritten
+CREATE TABLE IF NOT EXISTS price_observations (
+    id SERIAL PRIMARY KEY,
+    distributor TEXT NOT NULL,
+    manufacturer TEXT NOT NULL,
+    manufacturer_pn TEXT NOT NULL,
+    distributor_pn TEXT NOT NULL,
+    quantity INT NOT NULL, -- quantity the unit price was quoted for
+    unit_price DOUBLE PRECISION NOT NULL,
+    availability INT NOT NULL,
+    price_breaks TEXT, -- JSON array of {quantity, unit_price}
+    observed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS price_observations_part_idx ON price_observations (manufacturer_pn, manufacturer, observed_at);
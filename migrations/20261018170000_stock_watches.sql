@@ -0,0 +1,26 @@
+-- Add migration script here
+-- items that could not be bought, re-checked in the background until the required quantity is in stock
+CREATE TABLE IF NOT EXISTS stock_watches (
+    id SERIAL PRIMARY KEY,
+    order_id INT NOT NULL,
+    manufacturer TEXT NOT NULL,
+    manufacturer_pn TEXT NOT NULL,
+    created_by INT NOT NULL,
+    FOREIGN KEY (order_id, manufacturer, manufacturer_pn) REFERENCES order_items(order_id, manufacturer, manufacturer_pn) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_checked_at TIMESTAMPTZ,
+    notified_at TIMESTAMPTZ, -- set once the item is back in stock, the watch is then no longer checked
+    UNIQUE (order_id, manufacturer, manufacturer_pn)
+);
+
+-- in-app notifications, shown on the user homepage until dismissed
+CREATE TABLE IF NOT EXISTS notifications (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    message TEXT NOT NULL,
+    link TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    read BOOLEAN NOT NULL DEFAULT FALSE
+);
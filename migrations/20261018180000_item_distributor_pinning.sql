@@ -0,0 +1,4 @@
+-- Add migration script here
+-- distributor an item must be bought from (e.g. existing cart, sponsor agreement), optionally with the exact distributor part number
+ALTER TABLE order_items ADD COLUMN IF NOT EXISTS preferred_distributor TEXT;
+ALTER TABLE order_items ADD COLUMN IF NOT EXISTS pinned_distributor_pn TEXT;
@@ -0,0 +1,19 @@
+-- Add migration script here
+-- items bought outside the distributor APIs (mechanical parts, resin, Amazon, ...) with a manually entered price
+CREATE TABLE IF NOT EXISTS custom_order_items (
+    id SERIAL PRIMARY KEY,
+    order_id INT NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    vendor TEXT NOT NULL,
+    description TEXT NOT NULL,
+    url TEXT,
+    quantity INT NOT NULL,
+    unit_price DOUBLE PRECISION NOT NULL,
+    shipping DOUBLE PRECISION NOT NULL DEFAULT 0,
+    proposal TEXT NOT NULL,
+    FOREIGN KEY (proposal) REFERENCES proposals(name) ON UPDATE CASCADE,
+    project TEXT NOT NULL,
+    FOREIGN KEY (project) REFERENCES projects(name) ON UPDATE CASCADE
+);
+
+ALTER TABLE order_bom ADD COLUMN IF NOT EXISTS bom_file_custom BYTEA;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Mouser cart created through the Cart API when the order is confirmed
+ALTER TABLE orders ADD COLUMN mouser_cart_url TEXT;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Digi-Key MyLists list created when the order is confirmed
+ALTER TABLE orders ADD COLUMN digikey_list_url TEXT;
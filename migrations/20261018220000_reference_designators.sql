@@ -0,0 +1,3 @@
+-- Add migration script here
+-- KiCad reference designators of the item (e.g. "R1, R2, R5"), when imported from a BOM that has them
+ALTER TABLE order_items ADD COLUMN reference_designators TEXT;
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Budget allocated to each proposal and project, NULL when none has been set
+ALTER TABLE proposals ADD COLUMN budget DOUBLE PRECISION;
+ALTER TABLE projects ADD COLUMN budget DOUBLE PRECISION;
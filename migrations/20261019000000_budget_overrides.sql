@@ -0,0 +1,12 @@
+-- Add migration script here
+-- confirmations of orders that went over a proposal or project budget
+CREATE TABLE IF NOT EXISTS budget_overrides (
+    id SERIAL PRIMARY KEY,
+    order_id INT NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    user_id INT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    justification TEXT NOT NULL,
+    overruns TEXT NOT NULL, -- budgets exceeded, as shown to the user
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+-- the ready/confirmed booleans are replaced by the order lifecycle status
+ALTER TABLE orders ADD COLUMN status TEXT NOT NULL DEFAULT 'draft'
+    CHECK (status IN ('draft', 'submitted', 'approved', 'purchased', 'shipped', 'partially_received', 'received', 'closed', 'rejected', 'cancelled'));
+
+UPDATE orders SET status = CASE
+    WHEN confirmed THEN 'approved'
+    WHEN ready THEN 'submitted'
+    ELSE 'draft'
+END;
+
+ALTER TABLE orders DROP COLUMN ready;
+ALTER TABLE orders DROP COLUMN confirmed;
@@ -0,0 +1,18 @@
+-- Add migration script here
+-- quantity of each item delivered so far, the sum of its receipts
+ALTER TABLE order_items
+ADD COLUMN received_quantity INT NOT NULL DEFAULT 0;
+
+-- each delivery of an item, partial deliveries add several receipts
+CREATE TABLE IF NOT EXISTS item_receipts (
+    id SERIAL PRIMARY KEY,
+    order_id INT NOT NULL,
+    manufacturer TEXT NOT NULL,
+    manufacturer_pn TEXT NOT NULL,
+    FOREIGN KEY (order_id, manufacturer, manufacturer_pn) REFERENCES order_items(order_id, manufacturer, manufacturer_pn) ON DELETE CASCADE ON UPDATE CASCADE,
+    quantity INT NOT NULL CHECK (quantity > 0),
+    received_on DATE NOT NULL DEFAULT CURRENT_DATE,
+    received_by INT NOT NULL,
+    FOREIGN KEY (received_by) REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,27 @@
+-- Add migration script here
+-- components on hand in the lab, one row per part and storage location
+CREATE TABLE IF NOT EXISTS inventory_items (
+    id SERIAL PRIMARY KEY,
+    manufacturer TEXT NOT NULL,
+    manufacturer_pn TEXT NOT NULL,
+    location TEXT NOT NULL,
+    quantity INT NOT NULL DEFAULT 0 CHECK (quantity >= 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (manufacturer, manufacturer_pn, location)
+);
+
+-- every change of an inventory quantity, from received orders or adjusted by hand
+CREATE TABLE IF NOT EXISTS inventory_movements (
+    id SERIAL PRIMARY KEY,
+    inventory_item_id INT NOT NULL,
+    FOREIGN KEY (inventory_item_id) REFERENCES inventory_items(id) ON DELETE CASCADE,
+    change INT NOT NULL,
+    reason TEXT NOT NULL,
+    order_id INT,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE SET NULL,
+    user_id INT,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS inventory_items_part_idx ON inventory_items (manufacturer_pn);
@@ -0,0 +1,29 @@
+-- Add migration script here
+-- spend of each order on its proposals and projects, taken when the order is approved
+CREATE TABLE IF NOT EXISTS order_budget_spend (
+    order_id INT NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('proposal', 'project')),
+    name TEXT NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (order_id, kind, name)
+);
+
+-- orders approved before the snapshots existed keep the spend of their current sourcing, without shipping
+INSERT INTO order_budget_spend (order_id, kind, name, amount)
+SELECT order_id, kind, name, SUM(amount) FROM (
+    SELECT a.order_id, 'proposal' AS kind, i.proposal AS name, a.quantity * a.unit_price AS amount
+    FROM order_item_allocations a
+    JOIN order_items i ON i.order_id = a.order_id AND i.manufacturer = a.manufacturer AND i.manufacturer_pn = a.manufacturer_pn
+    UNION ALL
+    SELECT a.order_id, 'project', i.project, a.quantity * a.unit_price
+    FROM order_item_allocations a
+    JOIN order_items i ON i.order_id = a.order_id AND i.manufacturer = a.manufacturer AND i.manufacturer_pn = a.manufacturer_pn
+    UNION ALL
+    SELECT c.order_id, 'proposal', c.proposal, c.quantity * c.unit_price + c.shipping FROM custom_order_items c
+    UNION ALL
+    SELECT c.order_id, 'project', c.project, c.quantity * c.unit_price + c.shipping FROM custom_order_items c
+) spend
+JOIN orders o ON o.id = spend.order_id
+WHERE o.status IN ('approved', 'purchased', 'shipped', 'partially_received', 'received', 'closed')
+GROUP BY order_id, kind, name;
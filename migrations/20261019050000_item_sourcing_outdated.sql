@@ -0,0 +1,10 @@
+-- Add migration script here
+-- set when an item is added or changed, cleared when the BOM generation stores its offers
+ALTER TABLE order_items ADD COLUMN IF NOT EXISTS sourcing_outdated BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- items sourced before the flag existed are priced by their stored results
+UPDATE order_items i SET sourcing_outdated = FALSE
+WHERE EXISTS (
+    SELECT 1 FROM item_sourcing_results r
+    WHERE r.order_id = i.order_id AND r.manufacturer = i.manufacturer AND r.manufacturer_pn = i.manufacturer_pn
+);
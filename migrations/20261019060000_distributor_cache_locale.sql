@@ -0,0 +1,6 @@
+-- Add migration script here
+-- cached prices are only valid in the currency and site they were quoted in: entries without a locale are dropped
+DELETE FROM distributor_cache;
+ALTER TABLE distributor_cache ADD COLUMN IF NOT EXISTS locale TEXT NOT NULL DEFAULT '';
+ALTER TABLE distributor_cache DROP CONSTRAINT IF EXISTS distributor_cache_pkey;
+ALTER TABLE distributor_cache ADD PRIMARY KEY (distributor, manufacturer, manufacturer_pn, quantity_bracket, locale);
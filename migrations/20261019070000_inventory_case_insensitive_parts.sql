@@ -0,0 +1,25 @@
+-- Add migration script here
+-- parts are matched regardless of case ("TI" and "ti" are the same manufacturer): rows differing only by case are merged into the oldest one
+WITH ranked AS (
+    SELECT id, quantity, MIN(id) OVER (PARTITION BY LOWER(manufacturer), LOWER(manufacturer_pn), location) AS keep_id
+    FROM inventory_items
+), moved AS (
+    UPDATE inventory_movements m SET inventory_item_id = r.keep_id
+    FROM ranked r
+    WHERE m.inventory_item_id = r.id AND r.id <> r.keep_id
+)
+UPDATE inventory_items i SET quantity = totals.quantity
+FROM (SELECT keep_id, SUM(quantity) AS quantity FROM ranked GROUP BY keep_id HAVING COUNT(*) > 1) totals
+WHERE i.id = totals.keep_id;
+
+DELETE FROM inventory_items i
+WHERE EXISTS (
+    SELECT 1 FROM inventory_items k
+    WHERE k.id < i.id AND LOWER(k.manufacturer) = LOWER(i.manufacturer) AND LOWER(k.manufacturer_pn) = LOWER(i.manufacturer_pn) AND k.location = i.location
+);
+
+ALTER TABLE inventory_items DROP CONSTRAINT IF EXISTS inventory_items_manufacturer_manufacturer_pn_location_key;
+CREATE UNIQUE INDEX IF NOT EXISTS inventory_items_part_location_key ON inventory_items (LOWER(manufacturer), LOWER(manufacturer_pn), location);
+
+DROP INDEX IF EXISTS inventory_items_part_idx;
+CREATE INDEX IF NOT EXISTS inventory_items_part_idx ON inventory_items (LOWER(manufacturer_pn));
@@ -0,0 +1,241 @@
+//! Offline stand-in for the Mouser and Digi-Key APIs, serving recorded JSON responses.
+//!
+//! Point the backend at it with `MOUSER_API_URL` and `DIGIKEY_API_URL` (e.g. `http://127.0.0.1:3900`).
+//! Responses are read from `<MOCK_FIXTURES_DIR>/<distributor>/<manufacturer part number>.json`,
+//! unknown parts get an empty search result. Mouser carts and Digi-Key lists are accepted and get a fake key.
+//! Failures can be simulated with:
+//! - `MOCK_FAIL_EVERY=n`: every n-th search request answers 503,
+//! - `MOCK_LATENCY_MS=ms`: every request is delayed,
+//! - `MOCK_DIGIKEY_TOKEN_TTL=s`: lifetime of the issued Digi-Key tokens (expired tokens get a 401).
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{atomic::{AtomicUsize, Ordering}, Arc},
+    time::Duration,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Form, Json, Router,
+};
+use serde_json::{json, Value};
+use tokio::{sync::Mutex, time::Instant};
+
+#[derive(Clone)]
+struct MockState {
+    fixtures_dir: PathBuf,
+    fail_every: usize,
+    latency: Duration,
+    token_ttl: u64,
+    search_requests: Arc<AtomicUsize>,
+    carts: Arc<AtomicUsize>,
+    lists: Arc<AtomicUsize>,
+    tokens: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<T>().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+    let addr = std::env::var("MOCK_DISTRIBUTORS_ADDR").unwrap_or("127.0.0.1:3900".to_string());
+
+    let state = MockState {
+        fixtures_dir: PathBuf::from(env_or("MOCK_FIXTURES_DIR", "fixtures/mock_distributors".to_string())),
+        fail_every: env_or("MOCK_FAIL_EVERY", 0),
+        latency: Duration::from_millis(env_or("MOCK_LATENCY_MS", 0)),
+        token_ttl: env_or("MOCK_DIGIKEY_TOKEN_TTL", 600),
+        search_requests: Arc::new(AtomicUsize::new(0)),
+        carts: Arc::new(AtomicUsize::new(0)),
+        lists: Arc::new(AtomicUsize::new(0)),
+        tokens: Arc::new(Mutex::new(HashMap::new())),
+    };
+
+    let app = Router::new()
+        .route("/api/v1/search/keyword", post(mouser_search))
+        .route("/api/v1/cart/items/insert", post(mouser_cart_insert))
+        .route("/v1/oauth2/token", post(digikey_token))
+        .route("/products/v4/search/keyword", post(digikey_search))
+        .route("/mylists/v1/lists", post(digikey_create_list))
+        .route("/mylists/v1/lists/:list_id/parts", post(digikey_add_list_parts))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    println!("Mock distributors running on {addr:?}");
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// Applies the configured latency and tells whether this search request should fail.
+async fn simulate(state: &MockState) -> bool {
+    if !state.latency.is_zero() {
+        tokio::time::sleep(state.latency).await;
+    }
+    let count = state.search_requests.fetch_add(1, Ordering::SeqCst) + 1;
+    state.fail_every > 0 && count.is_multiple_of(state.fail_every)
+}
+
+/// Both APIs search by "<manufacturer> <part number>", the fixture is picked by the part number.
+fn load_fixture(state: &MockState, distributor: &str, keyword: &str) -> Option<Value> {
+    let manufacturer_pn = keyword.split_whitespace().last()?;
+    // never read outside the fixtures directory
+    if manufacturer_pn.contains('/') || manufacturer_pn.contains('\\') || manufacturer_pn.starts_with('.') {
+        return None;
+    }
+    let path = state.fixtures_dir.join(distributor).join(format!("{}.json", manufacturer_pn));
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+async fn mouser_search(
+    State(state): State<MockState>,
+    Query(params): Query<HashMap<String, String>>,
+    Json(body): Json<Value>,
+) -> Response {
+    if simulate(&state).await {
+        return (StatusCode::SERVICE_UNAVAILABLE, "simulated failure").into_response();
+    }
+    if params.get("apiKey").is_none_or(|key| key.is_empty()) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "Errors": [{ "Message": "Invalid unique identifier." }] }))).into_response();
+    }
+
+    let keyword = body["SearchByKeywordRequest"]["keyword"].as_str().unwrap_or_default();
+    let response = load_fixture(&state, "mouser", keyword).unwrap_or(json!({
+        "Errors": [],
+        "SearchResults": { "NumberOfResult": 0, "Parts": [] }
+    }));
+    Json(response).into_response()
+}
+
+/// Accepts every item and answers with a new cart key.
+async fn mouser_cart_insert(
+    State(state): State<MockState>,
+    Query(params): Query<HashMap<String, String>>,
+    Json(body): Json<Value>,
+) -> Response {
+    if simulate(&state).await {
+        return (StatusCode::SERVICE_UNAVAILABLE, "simulated failure").into_response();
+    }
+    if params.get("apiKey").is_none_or(|key| key.is_empty()) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "Errors": [{ "Message": "Invalid unique identifier." }] }))).into_response();
+    }
+
+    let cart_key = format!("mock-cart-{}", state.carts.fetch_add(1, Ordering::SeqCst) + 1);
+    let cart_items: Vec<Value> = body["CartItems"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| json!({
+            "MouserPartNumber": item["MouserPartNumber"],
+            "Quantity": item["Quantity"],
+            "Errors": [],
+        }))
+        .collect();
+    println!("Created Mouser cart {} with {} items", cart_key, cart_items.len());
+    Json(json!({
+        "Errors": [],
+        "CartKey": cart_key,
+        "CartItems": cart_items,
+    })).into_response()
+}
+
+async fn digikey_token(
+    State(state): State<MockState>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let has_credentials = ["client_id", "client_secret"]
+        .iter()
+        .all(|key| form.get(*key).is_some_and(|value| !value.is_empty()));
+    if !has_credentials {
+        return (StatusCode::UNAUTHORIZED, "missing client credentials").into_response();
+    }
+
+    let mut tokens = state.tokens.lock().await;
+    let token = format!("mock-token-{}", tokens.len() + 1);
+    tokens.insert(token.clone(), Instant::now() + Duration::from_secs(state.token_ttl));
+    println!("Issued Digi-Key token {}", token);
+    Json(json!({
+        "access_token": token,
+        "token_type": "Bearer",
+        "expires_in": state.token_ttl,
+    })).into_response()
+}
+
+/// Whether the request carries a Digi-Key token issued by the mock and not expired yet.
+async fn valid_digikey_token(state: &MockState, headers: &HeaderMap) -> bool {
+    let token = headers
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default()
+        .to_string();
+    state.tokens
+        .lock()
+        .await
+        .get(&token)
+        .is_some_and(|expires_at| Instant::now() < *expires_at)
+}
+
+async fn digikey_search(
+    State(state): State<MockState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !valid_digikey_token(&state, &headers).await {
+        return (StatusCode::UNAUTHORIZED, "invalid or expired token").into_response();
+    }
+    if simulate(&state).await {
+        return (StatusCode::SERVICE_UNAVAILABLE, "simulated failure").into_response();
+    }
+
+    let keyword = body["Keywords"].as_str().unwrap_or_default();
+    let response = load_fixture(&state, "digikey", keyword).unwrap_or(json!({
+        "Products": [],
+        "ProductsCount": 0,
+        "ExactMatches": []
+    }));
+    Json(response).into_response()
+}
+
+/// Answers with the id of the new list, as a JSON string.
+async fn digikey_create_list(
+    State(state): State<MockState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !valid_digikey_token(&state, &headers).await {
+        return (StatusCode::UNAUTHORIZED, "invalid or expired token").into_response();
+    }
+    if simulate(&state).await {
+        return (StatusCode::SERVICE_UNAVAILABLE, "simulated failure").into_response();
+    }
+
+    let list_id = format!("mock-list-{}", state.lists.fetch_add(1, Ordering::SeqCst) + 1);
+    println!("Created Digi-Key list {} ({})", list_id, body["ListName"].as_str().unwrap_or_default());
+    Json(json!(list_id)).into_response()
+}
+
+async fn digikey_add_list_parts(
+    State(state): State<MockState>,
+    Path(list_id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !valid_digikey_token(&state, &headers).await {
+        return (StatusCode::UNAUTHORIZED, "invalid or expired token").into_response();
+    }
+    if simulate(&state).await {
+        return (StatusCode::SERVICE_UNAVAILABLE, "simulated failure").into_response();
+    }
+
+    let parts = body.as_array().map_or(0, |parts| parts.len());
+    println!("Added {} parts to Digi-Key list {}", parts, list_id);
+    Json(json!([])).into_response()
+}
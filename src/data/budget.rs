@@ -0,0 +1,189 @@
+use sqlx::{PgExecutor, PgPool};
+use crate::data::{errors::DataError, order};
+use crate::models::distributor_models::DistributorKind;
+use crate::models::budget::{Budget, BudgetKind, BudgetOverrun, BudgetOverview, BudgetSpend};
+use crate::models::order_status::OrderStatus;
+
+fn approved_statuses() -> Vec<String> {
+    OrderStatus::approved_statuses()
+        .iter()
+        .map(|status| status.name().to_string())
+        .collect()
+}
+
+/// Budgets of the proposals or projects, with the spend snapshotted when their orders were approved.
+pub async fn get_budgets(pool: &PgPool, kind: BudgetKind) -> Result<Vec<Budget>, DataError> {
+    let budgets = sqlx::query_as!(
+        Budget,
+        r#"SELECT b.name AS "name!", b.budget AS allocated,
+            COALESCE((
+                SELECT SUM(s.amount)
+                FROM order_budget_spend s
+                JOIN orders o ON o.id = s.order_id
+                WHERE s.kind = $1 AND s.name = b.name AND o.status = ANY($2)
+            ), 0) AS "committed!"
+         FROM (
+            SELECT name, budget FROM proposals WHERE $1 = 'proposal'
+            UNION ALL
+            SELECT name, budget FROM projects WHERE $1 = 'project'
+         ) b
+         ORDER BY b.name"#,
+        kind.name(),
+        &approved_statuses()
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(budgets)
+}
+
+pub async fn get_budget_overview(pool: &PgPool) -> Result<BudgetOverview, DataError> {
+    Ok(BudgetOverview {
+        proposals: get_budgets(pool, BudgetKind::Proposal).await?,
+        projects: get_budgets(pool, BudgetKind::Project).await?,
+    })
+}
+
+/// Sets the budget of a proposal or project, `None` removes it.
+pub async fn set_budget(pool: &PgPool, kind: BudgetKind, name: &str, budget: Option<f64>) -> Result<(), DataError> {
+    let result = match kind {
+        BudgetKind::Proposal => sqlx::query!(
+            "UPDATE proposals SET budget = $1 WHERE name = $2",
+            budget,
+            name
+        )
+        .execute(pool)
+        .await,
+        BudgetKind::Project => sqlx::query!(
+            "UPDATE projects SET budget = $1 WHERE name = $2",
+            budget,
+            name
+        )
+        .execute(pool)
+        .await,
+    }
+    .map_err(DataError::Query)?;
+    if result.rows_affected() == 0 {
+        return Err(DataError::Internal(format!("No proposal or project named {}", name)));
+    }
+    Ok(())
+}
+
+/// Current spend of the order on each proposal or project: the sourced prices plus, for every distributor,
+/// its shipping and handling shared out by price, and the custom items with their shipping.
+pub async fn get_order_spend(pool: &PgPool, kind: BudgetKind, order_id: i32) -> Result<Vec<BudgetSpend>, DataError> {
+    let rows = sqlx::query!(
+        r#"SELECT CASE WHEN $2 = 'proposal' THEN i.proposal ELSE i.project END AS "name!",
+            a.distributor AS "distributor?", a.quantity * a.unit_price AS "amount!"
+         FROM order_item_allocations a
+         JOIN order_items i ON i.order_id = a.order_id AND i.manufacturer = a.manufacturer AND i.manufacturer_pn = a.manufacturer_pn
+         WHERE a.order_id = $1
+         UNION ALL
+         SELECT CASE WHEN $2 = 'proposal' THEN c.proposal ELSE c.project END, NULL, c.quantity * c.unit_price + c.shipping
+         FROM custom_order_items c
+         WHERE c.order_id = $1"#,
+        order_id,
+        kind.name()
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    let distributor_totals = order::get_cost_summary(pool, order_id).await?
+        .map(|summary| summary.distributors)
+        .unwrap_or_default();
+
+    let mut spend: Vec<BudgetSpend> = Vec::new();
+    for row in rows {
+        let charges = row.distributor
+            .as_deref()
+            .and_then(DistributorKind::from_name)
+            .and_then(|distributor| distributor_totals.iter().find(|total| total.distributor == distributor))
+            .filter(|total| total.subtotal > 0.0)
+            .map_or(0.0, |total| (total.shipping + total.handling) * row.amount / total.subtotal);
+        match spend.iter_mut().find(|spend| spend.name == row.name) {
+            Some(spend) => spend.amount += row.amount + charges,
+            None => spend.push(BudgetSpend { name: row.name, amount: row.amount + charges }),
+        }
+    }
+    Ok(spend)
+}
+
+/// Stores the spend of the order, which from now on counts against the budgets while the order stays approved.
+pub async fn record_order_spend<'e>(
+    executor: impl PgExecutor<'e>,
+    order_id: i32,
+    kind: BudgetKind,
+    spend: &[BudgetSpend],
+) -> Result<(), DataError> {
+    let names: Vec<String> = spend.iter().map(|spend| spend.name.clone()).collect();
+    let amounts: Vec<f64> = spend.iter().map(|spend| spend.amount).collect();
+    // a new approval replaces the spend of a previous one
+    sqlx::query!(
+        "WITH stale AS (
+            DELETE FROM order_budget_spend WHERE order_id = $1 AND kind = $2 AND NOT (name = ANY($3))
+         )
+         INSERT INTO order_budget_spend (order_id, kind, name, amount)
+         SELECT $1, $2, name, amount FROM UNNEST($3::TEXT[], $4::FLOAT8[]) AS spend(name, amount)
+         ON CONFLICT (order_id, kind, name) DO UPDATE SET amount = EXCLUDED.amount",
+        order_id,
+        kind.name(),
+        &names,
+        &amounts
+    )
+    .execute(executor)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+fn find_overruns(kind: BudgetKind, budgets: &[Budget], spend: &[BudgetSpend]) -> Vec<BudgetOverrun> {
+    spend
+        .iter()
+        .filter(|spend| spend.amount > 0.0)
+        .filter_map(|spend| {
+            let budget = budgets.iter().find(|budget| budget.name == spend.name)?;
+            let allocated = budget.allocated?;
+            if budget.committed + spend.amount <= allocated + 1e-9 {
+                return None;
+            }
+            Some(BudgetOverrun {
+                kind,
+                name: spend.name.clone(),
+                allocated,
+                committed: budget.committed,
+                order_spend: spend.amount,
+            })
+        })
+        .collect()
+}
+
+/// Budgets that approving the order would exceed with `spend`, the current spend of the order on the budgets of `kind`.
+/// The order itself must not be approved yet, otherwise its spend would be counted twice.
+pub async fn get_budget_overruns(pool: &PgPool, kind: BudgetKind, spend: &[BudgetSpend]) -> Result<Vec<BudgetOverrun>, DataError> {
+    Ok(find_overruns(kind, &get_budgets(pool, kind).await?, spend))
+}
+
+pub async fn record_budget_override<'e>(
+    executor: impl PgExecutor<'e>,
+    order_id: i32,
+    user_id: i32,
+    justification: &str,
+    overruns: &[BudgetOverrun],
+) -> Result<(), DataError> {
+    let overruns_text = overruns
+        .iter()
+        .map(|overrun| overrun.describe())
+        .collect::<Vec<String>>()
+        .join("; ");
+    sqlx::query!(
+        "INSERT INTO budget_overrides (order_id, user_id, justification, overruns) VALUES ($1, $2, $3, $4)",
+        order_id,
+        user_id,
+        justification,
+        overruns_text
+    )
+    .execute(executor)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
@@ -0,0 +1,21 @@
+/// Serializes a header and its rows as RFC 4180 CSV, quoting only the fields that need it.
+pub fn to_csv(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut csv = String::new();
+    let header_row: Vec<String> = header.iter().map(|field| escape_field(field)).collect();
+    csv.push_str(&header_row.join(","));
+    csv.push_str("\r\n");
+    for row in rows {
+        let row: Vec<String> = row.iter().map(|field| escape_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn escape_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') || field.contains('\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -1,10 +1,11 @@
-use reqwest::Client;
 use tokio::{sync::RwLock, time::Instant};
 use std::{sync::Arc, time::Duration};
-use dotenvy::dotenv;
 use crate::models::digikey_api_models::{
-    DigiKeyPart, DigiKeyRequestBody, DigiKeySearchResult, FilterOptionsRequest, Product, ProductVariation, SortOptions, TokenResponse
+    CreateListRequest, DigiKeyRequestBody, DigiKeySearchResult, FilterOptionsRequest, ListPart, ListPartQuantity, Product,
+    ProductVariation, SortOptions, TokenResponse
 };
+use crate::data::{distributor, errors::SourcingError, sourcing::{self, SourcingContext}};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, QuotePriceBreak};
 use serde_path_to_error::deserialize;
 use once_cell::sync::Lazy;
 
@@ -16,7 +17,7 @@ struct TokenCache {
 
 static DIGIKEY_TOKEN: Lazy<Arc<RwLock<Option<TokenCache>>>> = Lazy::new(|| Arc::new(RwLock::new(None)));
 
-async fn digikey_get_token() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+async fn digikey_get_token(sourcing: &SourcingContext) -> Result<String, SourcingError> {
     {
         let token_lock = DIGIKEY_TOKEN.read().await;
 
@@ -29,16 +30,14 @@ async fn digikey_get_token() -> Result<String, Box<dyn std::error::Error + Send
 
     let mut token_lock = DIGIKEY_TOKEN.write().await;
 
-    dotenv().ok();
-
-    let client_id = std::env::var("DIGIKEY_CLIENT_ID")?;
-    let client_secret = std::env::var("DIGIKEY_CLIENT_SECRET")?;
+    let client_id = sourcing::required_env("DIGIKEY_CLIENT_ID")?;
+    let client_secret = sourcing::required_env("DIGIKEY_CLIENT_SECRET")?;
 
     println!("🔐 Fetching new Digi-Key token...");
 
-    let client = Client::new();
-    let token_response = client
-        .post("https://api.digikey.com/v1/oauth2/token")
+    let _permit = sourcing.acquire(DistributorKind::DigiKey).await;
+    let token_response = sourcing.client
+        .post(format!("{}/v1/oauth2/token", DistributorKind::DigiKey.api_base_url()))
         .header("Content-Type", "application/x-www-form-urlencoded")
         .body(format!(
             "client_id={}&client_secret={}&grant_type=client_credentials",
@@ -48,11 +47,12 @@ async fn digikey_get_token() -> Result<String, Box<dyn std::error::Error + Send
         .await?;
 
     if !token_response.status().is_success() {
-        return Err(format!(
-            "Failed to get Digi-Key token: {}",
-            token_response.text().await?
-        )
-        .into());
+        let status = token_response.status();
+        return Err(match status.as_u16() {
+            // the token endpoint answers 400/401 for wrong client credentials
+            400 | 401 | 403 => SourcingError::Auth(format!("Failed to get Digi-Key token: {}", token_response.text().await?)),
+            _ => SourcingError::from_status(status, "Failed to get Digi-Key token"),
+        });
     }
 
     let token: TokenResponse = token_response.json().await?;
@@ -66,26 +66,40 @@ async fn digikey_get_token() -> Result<String, Box<dyn std::error::Error + Send
     Ok(token.access_token)
 }
 
+/// Drops the cached token, so that the next request fetches a new one.
+async fn digikey_invalidate_token() {
+    let mut token_lock = DIGIKEY_TOKEN.write().await;
+    *token_lock = None;
+}
+
+/// Currency and site of the requests, `DIGIKEY_LOCALE_CURRENCY` and `DIGIKEY_LOCALE_SITE` override the pricing settings.
+pub fn locale(sourcing: &SourcingContext) -> (String, String) {
+    let currency = std::env::var("DIGIKEY_LOCALE_CURRENCY").unwrap_or(sourcing.pricing.currency.clone());
+    let site = std::env::var("DIGIKEY_LOCALE_SITE").unwrap_or(sourcing.pricing.country.clone());
+    (currency, site)
+}
+
 pub async fn digikey_search(
+    sourcing: &SourcingContext,
     query_manufacturer: &str, 
     query_manufacturer_pn: &str, 
     quantity: u32
-) -> Result<Option<DigiKeyPart>, Box<dyn std::error::Error + Send + Sync>> {
-    dotenv().ok();
-    let client_id = std::env::var("DIGIKEY_CLIENT_ID").expect("DIGIKEY_CLIENT_ID not set");
+) -> Result<Option<DistributorQuote>, SourcingError> {
+    let client_id = sourcing::required_env("DIGIKEY_CLIENT_ID")?;
+    let language = std::env::var("DIGIKEY_LOCALE_LANGUAGE").unwrap_or(sourcing.pricing.language.clone());
+    let (currency, site) = locale(sourcing);
     println!("Searching for {} {} on Digikey", query_manufacturer, query_manufacturer_pn);
 
-    let token = digikey_get_token().await?;
-    let client = Client::new();
     // Step 2: Perform product search
-    let url = format!("https://api.digikey.com/products/v4/search/keyword");
+    let url = format!("{}/products/v4/search/keyword", DistributorKind::DigiKey.api_base_url());
 
     let request_body = DigiKeyRequestBody {
         keywords: format!("{} {}", query_manufacturer, query_manufacturer_pn).into(),
         limit: 20,
         offset: 0,
         filter_options_request: FilterOptionsRequest {
-            minimum_quantity_available: quantity,
+            // partial stock is still useful when the quantity is split across distributors
+            minimum_quantity_available: 1,
             market_place_filter: "NoFilter".to_string(),
         },
         sort_options: SortOptions {
@@ -94,22 +108,37 @@ pub async fn digikey_search(
         },
     };
 
-    let search_response = client
-        .post(&url)
-        .header("X-DIGIKEY-Client-Id", client_id)
-        .header("X-DIGIKEY-Locale-Language", "en")
-        .header("X-DIGIKEY-Locale-Currency", "EUR")
-        .header("X-DIGIKEY-Locale-Site", "IT")
-        .header("accept", "application/json")
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&request_body)
-        .timeout(Duration::from_secs(100))
-        .send()
-        .await?;
+    let mut search_response;
+    let mut token_refreshed = false;
+    loop {
+        let token = digikey_get_token(sourcing).await?;
+        let _permit = sourcing.acquire(DistributorKind::DigiKey).await;
+        search_response = sourcing.client
+            .post(&url)
+            .header("X-DIGIKEY-Client-Id", &client_id)
+            .header("X-DIGIKEY-Locale-Language", &language)
+            .header("X-DIGIKEY-Locale-Currency", &currency)
+            .header("X-DIGIKEY-Locale-Site", &site)
+            .header("accept", "application/json")
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", token))
+            .json(&request_body)
+            .timeout(Duration::from_secs(100))
+            .send()
+            .await?;
+
+        // the token may have been revoked before its expiry: get a new one and try once more
+        if search_response.status() == reqwest::StatusCode::UNAUTHORIZED && !token_refreshed {
+            println!("Digi-Key token rejected, fetching a new one");
+            digikey_invalidate_token().await;
+            token_refreshed = true;
+            continue;
+        }
+        break;
+    }
 
     if !search_response.status().is_success() {
-        return Err(format!("Failed to search DigiKey: code {:?}", search_response.status()).into());
+        return Err(SourcingError::from_status(search_response.status(), "Failed to search DigiKey"));
     }
 
     let bytes = search_response.bytes().await?;
@@ -132,7 +161,7 @@ pub async fn digikey_search(
         },
         Err(e) => {
             eprintln!("❌ Path error: {}", e);
-            return Err(format!("Error parsing JSON: {}", e).into());
+            return Err(SourcingError::Parse(e.to_string()));
         }
     };
 
@@ -142,42 +171,126 @@ pub async fn digikey_search(
         return Ok(None);
     }
 
-    let mut valid_variations: Vec<(&Product, &ProductVariation)> = Vec::new();
+    // assure we return only the requested item, one quote for each packaging variation
+    let mut quotes: Vec<DistributorQuote> = Vec::new();
     for product in &possible_products {
         for variation in &product.product_variations {
-            if variation.quantity_availablefor_package_type >= quantity
-                && variation.minimum_order_quantity <= quantity
-                && (&product.manufacturer_product_number == query_manufacturer_pn || query_manufacturer_pn == variation.digi_key_product_number)
-            {
-                valid_variations.push((product, variation));
+            if product.manufacturer_product_number == query_manufacturer_pn || query_manufacturer_pn == variation.digi_key_product_number {
+                quotes.push(variation_to_quote(product, variation, quantity));
             }
         }
     }
 
-    if valid_variations.is_empty() {
-        return Ok(None);
+    if quotes.is_empty() {
+        let mut similar_parts: Vec<String> = Vec::new();
+        for product in &possible_products {
+            if !similar_parts.contains(&product.manufacturer_product_number) {
+                similar_parts.push(product.manufacturer_product_number.clone());
+            }
+        }
+        similar_parts.truncate(5);
+        return Err(SourcingError::Ambiguous(similar_parts));
+    }
+
+    Ok(distributor::pick_offer(quotes, quantity))
+}
+
+/// A part to add to a Digi-Key list.
+pub struct ListRow {
+    pub quantity: i32,
+    pub digikey_pn: String,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+}
+
+/// Sends an authorized JSON POST to the Digi-Key API, fetching a new token once if the cached one is rejected.
+async fn digikey_post<T: serde::Serialize>(
+    sourcing: &SourcingContext,
+    url: &str,
+    body: &T,
+) -> Result<reqwest::Response, SourcingError> {
+    let client_id = sourcing::required_env("DIGIKEY_CLIENT_ID")?;
+    let mut token_refreshed = false;
+    loop {
+        let token = digikey_get_token(sourcing).await?;
+        let _permit = sourcing.acquire(DistributorKind::DigiKey).await;
+        let response = sourcing.client
+            .post(url)
+            .header("X-DIGIKEY-Client-Id", &client_id)
+            .header("accept", "application/json")
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", token))
+            .json(body)
+            .timeout(Duration::from_secs(100))
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED && !token_refreshed {
+            println!("Digi-Key token rejected, fetching a new one");
+            digikey_invalidate_token().await;
+            token_refreshed = true;
+            continue;
+        }
+        return Ok(response);
     }
+}
 
-    let best_pair = valid_variations
-        .into_iter()
-        .min_by(|(_, v1), (_, v2)| {
-            let p1 = v1.get_price(quantity).unwrap_or(f64::INFINITY);
-            let p2 = v2.get_price(quantity).unwrap_or(f64::INFINITY);
-            p1.partial_cmp(&p2).unwrap_or(std::cmp::Ordering::Equal)
+/// Creates a MyLists list called `list_name` with the given parts and returns its id.
+pub async fn create_digikey_list(
+    sourcing: &SourcingContext,
+    list_name: &str,
+    rows: &[ListRow],
+) -> Result<String, SourcingError> {
+    let lists_url = format!("{}/mylists/v1/lists", DistributorKind::DigiKey.api_base_url());
+    let list_response = digikey_post(sourcing, &lists_url, &CreateListRequest { list_name: list_name.to_string() }).await?;
+    if !list_response.status().is_success() {
+        return Err(SourcingError::from_status(list_response.status(), "Failed to create the Digi-Key list"));
+    }
+    // the API answers with the id of the new list as a JSON string
+    let list_id: String = list_response.json().await?;
+
+    let parts: Vec<ListPart> = rows
+        .iter()
+        .map(|row| ListPart {
+            requested_part_number: row.digikey_pn.clone(),
+            manufacturer_name: row.manufacturer.clone(),
+            customer_reference: row.manufacturer_pn.clone(),
+            quantities: vec![ListPartQuantity { quantity: row.quantity.max(0) as u32 }],
         })
-        .unwrap();
-
-    let (best_product, best_variation) = best_pair;
-
-    let product = DigiKeyPart {
-        manufacturer: best_product.manufacturer.name.clone(),
-        manufacturer_pn: best_product.manufacturer_product_number.clone(),
-        description: best_product.description.product_description.clone(),
-        digikey_pn: best_variation.digi_key_product_number.clone(),
-        product_url: best_product.product_url.clone(),
-        unit_price: best_variation.get_price(quantity).unwrap_or(0.0),
-        availability: best_product.quantity_available,
-    };
+        .collect();
+    let parts_url = format!("{}/{}/parts", lists_url, list_id);
+    let parts_response = digikey_post(sourcing, &parts_url, &parts).await?;
+    if !parts_response.status().is_success() {
+        return Err(SourcingError::from_status(parts_response.status(), "Failed to add the parts to the Digi-Key list"));
+    }
+    Ok(list_id)
+}
 
-    Ok(Some(product))
+/// Link to a Digi-Key list, on the website set by `DIGIKEY_LIST_PAGE_URL` (default https://www.digikey.it/mylists/list/).
+pub fn digikey_list_url(list_id: &str) -> String {
+    let list_page = std::env::var("DIGIKEY_LIST_PAGE_URL").unwrap_or("https://www.digikey.it/mylists/list/".to_string());
+    format!("{}{}", list_page, list_id)
+}
+
+fn variation_to_quote(product: &Product, variation: &ProductVariation, quantity: u32) -> DistributorQuote {
+    DistributorQuote {
+        distributor: DistributorKind::DigiKey,
+        manufacturer: product.manufacturer.name.clone(),
+        manufacturer_pn: product.manufacturer_product_number.clone(),
+        description: product.description.product_description.clone(),
+        distributor_pn: variation.digi_key_product_number.clone(),
+        product_url: product.product_url.clone(),
+        unit_price: variation.get_price(quantity).unwrap_or(0.0),
+        availability: variation.quantity_availablefor_package_type,
+        minimum_order_quantity: variation.minimum_order_quantity,
+        price_breaks: variation.standard_pricing
+            .clone()
+            .unwrap_or_default()
+            .iter()
+            .map(|price| QuotePriceBreak {
+                quantity: price.break_quantity,
+                unit_price: price.unit_price,
+            })
+            .collect(),
+    }
 }
@@ -0,0 +1,258 @@
+use futures::future::{join_all, BoxFuture};
+use sqlx::PgPool;
+use crate::data::{distributor_cache, digikey_apis, farnell_apis, lcsc_apis, mouser_apis, tme_apis};
+use crate::data::{errors::SourcingError, sourcing::SourcingContext};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, Offer};
+use crate::models::item::OrderItem;
+
+pub type SearchResult = Result<Option<DistributorQuote>, SourcingError>;
+
+/// A parts supplier that can be queried for a manufacturer part number.
+/// Adding a new supplier only requires implementing this trait and registering it in `default_distributors`.
+pub trait Distributor: Send + Sync {
+    fn kind(&self) -> DistributorKind;
+
+    fn search<'a>(
+        &'a self,
+        sourcing: &'a SourcingContext,
+        manufacturer: &'a str,
+        manufacturer_pn: &'a str,
+        quantity: u32,
+    ) -> BoxFuture<'a, SearchResult>;
+
+    /// Searches by the distributor's own part number, for items pinned to it.
+    /// Distributors whose search accepts both kinds of part number keep the default.
+    fn search_distributor_pn<'a>(
+        &'a self,
+        sourcing: &'a SourcingContext,
+        manufacturer: &'a str,
+        distributor_pn: &'a str,
+        quantity: u32,
+    ) -> BoxFuture<'a, SearchResult> {
+        self.search(sourcing, manufacturer, distributor_pn, quantity)
+    }
+
+    /// Currency and site the prices are quoted in, cached quotes are only reused for the same locale.
+    fn locale(&self, sourcing: &SourcingContext) -> String {
+        format!("{}-{}", sourcing.pricing.currency, sourcing.pricing.country)
+    }
+
+    /// Quantity the cached quotes are stored under, a power-of-two bracket unless the answer depends on the exact quantity.
+    fn cache_quantity(&self, quantity: u32) -> i32 {
+        distributor_cache::quantity_bracket(quantity)
+    }
+}
+
+pub struct MouserDistributor;
+
+impl Distributor for MouserDistributor {
+    fn kind(&self) -> DistributorKind {
+        DistributorKind::Mouser
+    }
+
+    fn search<'a>(&'a self, sourcing: &'a SourcingContext, manufacturer: &'a str, manufacturer_pn: &'a str, quantity: u32) -> BoxFuture<'a, SearchResult> {
+        Box::pin(mouser_apis::search_mouser(sourcing, manufacturer, manufacturer_pn, quantity))
+    }
+}
+
+pub struct DigiKeyDistributor;
+
+impl Distributor for DigiKeyDistributor {
+    fn kind(&self) -> DistributorKind {
+        DistributorKind::DigiKey
+    }
+
+    fn search<'a>(&'a self, sourcing: &'a SourcingContext, manufacturer: &'a str, manufacturer_pn: &'a str, quantity: u32) -> BoxFuture<'a, SearchResult> {
+        Box::pin(digikey_apis::digikey_search(sourcing, manufacturer, manufacturer_pn, quantity))
+    }
+
+    fn locale(&self, sourcing: &SourcingContext) -> String {
+        let (currency, site) = digikey_apis::locale(sourcing);
+        format!("{}-{}", currency, site)
+    }
+
+    /// The packaging variation is chosen for the exact quantity.
+    fn cache_quantity(&self, quantity: u32) -> i32 {
+        quantity as i32
+    }
+}
+
+pub struct FarnellDistributor;
+
+impl Distributor for FarnellDistributor {
+    fn kind(&self) -> DistributorKind {
+        DistributorKind::Farnell
+    }
+
+    fn search<'a>(&'a self, sourcing: &'a SourcingContext, manufacturer: &'a str, manufacturer_pn: &'a str, quantity: u32) -> BoxFuture<'a, SearchResult> {
+        Box::pin(farnell_apis::search_farnell(sourcing, manufacturer, manufacturer_pn, quantity))
+    }
+
+    fn search_distributor_pn<'a>(&'a self, sourcing: &'a SourcingContext, manufacturer: &'a str, distributor_pn: &'a str, quantity: u32) -> BoxFuture<'a, SearchResult> {
+        Box::pin(farnell_apis::search_farnell_sku(sourcing, manufacturer, distributor_pn, quantity))
+    }
+
+    /// Farnell prices in the currency of the store.
+    fn locale(&self, _sourcing: &SourcingContext) -> String {
+        farnell_apis::store()
+    }
+}
+
+pub struct LcscDistributor;
+
+impl Distributor for LcscDistributor {
+    fn kind(&self) -> DistributorKind {
+        DistributorKind::Lcsc
+    }
+
+    fn search<'a>(&'a self, sourcing: &'a SourcingContext, manufacturer: &'a str, manufacturer_pn: &'a str, quantity: u32) -> BoxFuture<'a, SearchResult> {
+        Box::pin(lcsc_apis::search_lcsc(sourcing, manufacturer, manufacturer_pn, quantity))
+    }
+
+    fn locale(&self, sourcing: &SourcingContext) -> String {
+        lcsc_apis::currency(sourcing)
+    }
+}
+
+pub struct TmeDistributor;
+
+impl Distributor for TmeDistributor {
+    fn kind(&self) -> DistributorKind {
+        DistributorKind::Tme
+    }
+
+    fn search<'a>(&'a self, sourcing: &'a SourcingContext, manufacturer: &'a str, manufacturer_pn: &'a str, quantity: u32) -> BoxFuture<'a, SearchResult> {
+        Box::pin(tme_apis::search_tme(sourcing, manufacturer, manufacturer_pn, quantity))
+    }
+
+    fn locale(&self, sourcing: &SourcingContext) -> String {
+        let (country, currency) = tme_apis::locale(sourcing);
+        format!("{}-{}", currency, country)
+    }
+}
+
+/// Distributors in order of preference: on equal price, the first one wins.
+pub fn default_distributors() -> Vec<Box<dyn Distributor>> {
+    vec![
+        Box::new(MouserDistributor),
+        Box::new(DigiKeyDistributor),
+        Box::new(FarnellDistributor),
+        Box::new(LcscDistributor),
+        Box::new(TmeDistributor),
+    ]
+}
+
+/// The default distributors whose credentials are configured in `sourcing`.
+pub fn enabled_distributors(sourcing: &SourcingContext) -> Vec<Box<dyn Distributor>> {
+    default_distributors()
+        .into_iter()
+        .filter(|distributor| sourcing.is_enabled(distributor.kind()))
+        .collect()
+}
+
+/// Queries every distributor concurrently (going through the cache). Returns the quotes that were found
+/// and, for every other distributor, why there is no quote (`SourcingError::NotFound` when the part is simply not sold there).
+/// An item pinned to a distributor is only searched there, by its pinned distributor part number when it has one.
+pub async fn search_all(
+    pool: &PgPool,
+    sourcing: &SourcingContext,
+    distributors: &[Box<dyn Distributor>],
+    item: &OrderItem,
+    force_refresh: bool,
+) -> (Vec<DistributorQuote>, Vec<(DistributorKind, SourcingError)>) {
+    let pinned = item.pinned_distributor();
+    if let Some(kind) = pinned.filter(|kind| !sourcing.is_enabled(*kind)) {
+        return (Vec::new(), vec![(kind, SourcingError::Config(format!("{} credentials are not set", kind.name())))]);
+    }
+    let distributors: Vec<&Box<dyn Distributor>> = distributors
+        .iter()
+        .filter(|distributor| pinned.is_none_or(|kind| distributor.kind() == kind))
+        .collect();
+    let searches = distributors
+        .iter()
+        .map(|distributor| distributor_cache::cached_search(
+            pool,
+            sourcing,
+            distributor.as_ref(),
+            item,
+            force_refresh,
+        ));
+
+    let mut quotes: Vec<DistributorQuote> = Vec::new();
+    let mut errors: Vec<(DistributorKind, SourcingError)> = Vec::new();
+    for (result, distributor) in join_all(searches).await.into_iter().zip(distributors) {
+        match result {
+            // the distributor may answer with another packaging than the pinned one
+            Ok(Some(quote)) if item.search_pn() != item.manufacturer_pn && !quote.distributor_pn.eq_ignore_ascii_case(item.search_pn()) => {
+                errors.push((distributor.kind(), SourcingError::Ambiguous(vec![quote.distributor_pn])));
+            },
+            Ok(Some(quote)) => quotes.push(quote),
+            Ok(None) => errors.push((distributor.kind(), SourcingError::NotFound)),
+            Err(e) => {
+                println!("{} search failed for {} {}: {}", distributor.kind().name(), item.manufacturer, item.search_pn(), e);
+                errors.push((distributor.kind(), e));
+            }
+        }
+    }
+    (quotes, errors)
+}
+
+/// Among several offers from the same distributor, prefers the cheapest one covering the quantity,
+/// falling back to the one with the most stock so that it can still cover part of a split order.
+pub fn pick_offer(quotes: Vec<DistributorQuote>, quantity: u32) -> Option<DistributorQuote> {
+    let best = select_best_offer(&quotes, quantity).map(|offer| offer.quote);
+    best.or(quotes.into_iter().max_by_key(|quote| quote.availability))
+}
+
+/// Covers the quantity with several distributors when none of them has enough stock alone,
+/// taking as much as possible from the cheapest ones first. Returns `None` when even the combined
+/// stock is not enough.
+pub fn split_across_distributors(quotes: &[DistributorQuote], quantity: u32) -> Option<Vec<Offer>> {
+    let mut candidates: Vec<&DistributorQuote> = quotes
+        .iter()
+        .filter(|quote| quote.availability > 0 && quote.unit_price_for(quote.availability.min(quantity)).is_some_and(|p| p > 0.0))
+        .collect();
+    candidates.sort_by(|q1, q2| {
+        let p1 = q1.unit_price_for(q1.availability.min(quantity)).unwrap_or(f64::INFINITY);
+        let p2 = q2.unit_price_for(q2.availability.min(quantity)).unwrap_or(f64::INFINITY);
+        p1.partial_cmp(&p2).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut remaining = quantity;
+    let mut offers: Vec<Offer> = Vec::new();
+    for quote in candidates {
+        if remaining == 0 {
+            break;
+        }
+        // buying less than the MOQ is not possible, buying more is fine as long as it is in stock
+        let share = remaining.min(quote.availability).max(quote.minimum_order_quantity);
+        if share > quote.availability {
+            continue;
+        }
+        if let Some(unit_price) = quote.unit_price_for(share).filter(|p| *p > 0.0) {
+            offers.push(Offer {
+                quote: quote.clone(),
+                quantity: share,
+                unit_price,
+            });
+            remaining = remaining.saturating_sub(share);
+        }
+    }
+
+    match remaining {
+        0 => Some(offers),
+        _ => None,
+    }
+}
+
+/// Picks the offer with the lowest extended price that can supply the whole quantity,
+/// taking price breaks and minimum order quantities into account.
+pub fn select_best_offer(quotes: &[DistributorQuote], quantity: u32) -> Option<Offer> {
+    quotes
+        .iter()
+        .filter_map(|quote| quote.offer_for(quantity))
+        .fold(None, |best: Option<Offer>, offer| match best {
+            Some(best) if best.extended_price() <= offer.extended_price() => Some(best),
+            _ => Some(offer),
+        })
+}
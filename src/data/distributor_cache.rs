@@ -0,0 +1,136 @@
+use dotenvy::dotenv;
+use sqlx::PgPool;
+use crate::data::distributor::{Distributor, SearchResult};
+use crate::data::errors::DataError;
+use crate::data::price_history;
+use crate::data::sourcing::SourcingContext;
+use crate::models::distributor_models::DistributorQuote;
+use crate::models::item::OrderItem;
+
+/// Quantities are grouped in power-of-two brackets, so that 80 and 100 pieces share the same cache entry.
+pub fn quantity_bracket(quantity: u32) -> i32 {
+    quantity.max(1).next_power_of_two() as i32
+}
+
+fn cache_ttl_hours() -> i32 {
+    dotenv().ok();
+    std::env::var("DISTRIBUTOR_CACHE_TTL_HOURS")
+        .ok()
+        .and_then(|ttl| ttl.parse::<i32>().ok())
+        .unwrap_or(24)
+}
+
+/// Entry of the cache: a search of a part on a distributor, for a quantity bracket and a locale.
+struct CacheKey<'a> {
+    distributor: &'a str,
+    manufacturer: &'a str,
+    manufacturer_pn: &'a str,
+    quantity_bracket: i32,
+    locale: String, // currency and site of the prices, e.g. EUR-IT
+}
+
+/// Searches a distributor, answering from the cache when a fresh enough result exists.
+/// `force_refresh` skips the lookup but still stores the new result.
+/// A pinned item is searched by its pinned distributor part number when it has one.
+/// Every live result is also added to the price history.
+pub async fn cached_search(
+    pool: &PgPool,
+    sourcing: &SourcingContext,
+    distributor: &dyn Distributor,
+    item: &OrderItem,
+    force_refresh: bool,
+) -> SearchResult {
+    let manufacturer = item.manufacturer.as_str();
+    let manufacturer_pn = item.search_pn();
+    let quantity = item.quantity as u32;
+    let distributor_name = distributor.kind().name();
+    let key = CacheKey {
+        distributor: distributor_name,
+        manufacturer,
+        manufacturer_pn,
+        quantity_bracket: distributor.cache_quantity(quantity),
+        locale: distributor.locale(sourcing),
+    };
+
+    if !force_refresh {
+        match get_cached_quote(pool, &key).await {
+            Ok(Some(cached)) => {
+                println!("Cache hit for {} {} on {}", manufacturer, manufacturer_pn, distributor_name);
+                return Ok(cached.map(|mut quote| {
+                    quote.unit_price = quote.unit_price_for(quantity).unwrap_or(0.0);
+                    quote
+                }));
+            },
+            Ok(None) => {},
+            Err(e) => println!("Failed reading distributor cache: {}", e),
+        }
+    }
+
+    let result = match manufacturer_pn != item.manufacturer_pn {
+        true => distributor.search_distributor_pn(sourcing, manufacturer, manufacturer_pn, quantity).await?,
+        false => distributor.search(sourcing, manufacturer, manufacturer_pn, quantity).await?,
+    };
+    if let Err(e) = store_quote(pool, &key, result.as_ref()).await {
+        println!("Failed writing distributor cache: {}", e);
+    }
+    if let Some(quote) = &result {
+        let recorded = price_history::record_observation(pool, quote, quantity).await;
+        if let Err(e) = recorded {
+            println!("Failed recording price history: {}", e);
+        }
+    }
+    Ok(result)
+}
+
+/// Returns `Some(None)` for a cached "not found", `None` when there is no fresh entry.
+async fn get_cached_quote(pool: &PgPool, key: &CacheKey<'_>) -> Result<Option<Option<DistributorQuote>>, DataError> {
+    let record = sqlx::query!(
+        "SELECT quote FROM distributor_cache
+         WHERE distributor = $1 AND manufacturer = $2 AND manufacturer_pn = $3 AND quantity_bracket = $4 AND locale = $5
+         AND fetched_at > NOW() - make_interval(hours => $6)",
+        key.distributor,
+        key.manufacturer,
+        key.manufacturer_pn,
+        key.quantity_bracket,
+        key.locale,
+        cache_ttl_hours()
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DataError::Query)?;
+
+    match record {
+        Some(record) => match record.quote {
+            Some(quote) => {
+                let quote: DistributorQuote = serde_json::from_str(&quote)
+                    .map_err(|e| DataError::Internal(e.to_string()))?;
+                Ok(Some(Some(quote)))
+            },
+            None => Ok(Some(None)),
+        },
+        None => Ok(None),
+    }
+}
+
+async fn store_quote(pool: &PgPool, key: &CacheKey<'_>, quote: Option<&DistributorQuote>) -> Result<(), DataError> {
+    let serialized = match quote {
+        Some(quote) => Some(serde_json::to_string(quote).map_err(|e| DataError::Internal(e.to_string()))?),
+        None => None,
+    };
+    sqlx::query!(
+        "INSERT INTO distributor_cache (distributor, manufacturer, manufacturer_pn, quantity_bracket, locale, quote, fetched_at)
+         VALUES ($1, $2, $3, $4, $5, $6, NOW())
+         ON CONFLICT (distributor, manufacturer, manufacturer_pn, quantity_bracket, locale)
+         DO UPDATE SET quote = EXCLUDED.quote, fetched_at = EXCLUDED.fetched_at",
+        key.distributor,
+        key.manufacturer,
+        key.manufacturer_pn,
+        key.quantity_bracket,
+        key.locale,
+        serialized
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
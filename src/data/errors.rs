@@ -30,6 +30,83 @@ pub enum DataError {
     TokenError(String),
 }
 
+/// Why a distributor search did not return a quote.
+#[derive(Error, Debug, Clone)]
+pub enum SourcingError {
+    #[error("authentication failed: {0}")]
+    Auth(String),
+
+    #[error("request quota exceeded: {0}")]
+    Quota(String),
+
+    #[error("request timed out")]
+    Timeout,
+
+    #[error("network error: {0}")]
+    Network(String),
+
+    #[error("unexpected response: {0}")]
+    Http(String),
+
+    #[error("failed to parse the response: {0}")]
+    Parse(String),
+
+    #[error("part not found")]
+    NotFound,
+
+    #[error("no exact match for the part number, similar parts: {}", .0.join(", "))]
+    Ambiguous(Vec<String>),
+
+    #[error("missing configuration: {0}")]
+    Config(String),
+}
+
+impl SourcingError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SourcingError::Auth(_) => "auth",
+            SourcingError::Quota(_) => "quota",
+            SourcingError::Timeout => "timeout",
+            SourcingError::Network(_) => "network",
+            SourcingError::Http(_) => "http",
+            SourcingError::Parse(_) => "parse",
+            SourcingError::NotFound => "not_found",
+            SourcingError::Ambiguous(_) => "ambiguous",
+            SourcingError::Config(_) => "config",
+        }
+    }
+
+    /// Whether trying again later may succeed; otherwise the part number or the configuration has to be fixed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            SourcingError::Quota(_) | SourcingError::Timeout | SourcingError::Network(_) | SourcingError::Http(_) | SourcingError::Parse(_)
+        )
+    }
+
+    /// Classifies a non-successful HTTP status.
+    pub fn from_status(status: reqwest::StatusCode, context: &str) -> SourcingError {
+        match status.as_u16() {
+            401 | 403 => SourcingError::Auth(format!("{} (code {})", context, status)),
+            429 => SourcingError::Quota(format!("{} (code {})", context, status)),
+            408 | 504 => SourcingError::Timeout,
+            _ => SourcingError::Http(format!("{} (code {})", context, status)),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SourcingError {
+    fn from(e: reqwest::Error) -> SourcingError {
+        if e.is_timeout() {
+            SourcingError::Timeout
+        } else if e.is_decode() {
+            SourcingError::Parse(e.to_string())
+        } else {
+            SourcingError::Network(e.to_string())
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error")]
@@ -1,14 +1,44 @@
-use umya_spreadsheet::{writer, reader, Spreadsheet};
+use umya_spreadsheet::{writer, reader, Cell, Spreadsheet};
 use std::io::Cursor;
+use crate::models::{item::CustomOrderItem, pricing::PricingSettings};
 
 #[derive(Debug, Clone)]
 pub struct KiCadItem {
     pub quantity: i32,
     pub manifacturer: String,
     pub manifacturer_pn: String,
+    pub reference_designators: Option<String>,
 }
 
-pub fn create_bom_file() -> Spreadsheet {
+/// A row of a distributor BOM: what is bought there, next to what the order asked for.
+#[derive(Debug, Clone)]
+pub struct BomRow {
+    pub manifacturer: String,
+    pub manifacturer_pn: String,
+    pub quantity: i32,
+    pub description: String,
+    pub unit_price: f64,
+    pub proposal: String,
+    pub link: String,
+    pub project: String,
+    pub delivered: String,
+    pub requested_quantity: i32,
+    pub notes: String,
+}
+
+/// Writes an amount as a number shown in the configured currency.
+fn set_amount(cell: &mut Cell, amount: f64, pricing: &PricingSettings) {
+    cell.set_value_number(amount);
+    cell.get_style_mut().get_number_format_mut().set_format_code(pricing.currency_format());
+}
+
+/// Writes a formula whose result is shown in the configured currency.
+fn set_amount_formula(cell: &mut Cell, formula: String, pricing: &PricingSettings) {
+    cell.set_formula(formula);
+    cell.get_style_mut().get_number_format_mut().set_format_code(pricing.currency_format());
+}
+
+pub fn create_bom_file(pricing: &PricingSettings) -> Spreadsheet {
     let mut book: Spreadsheet = umya_spreadsheet::new_file();
     let _sheet = book.new_sheet("Ordine").unwrap();
 
@@ -21,51 +51,137 @@ pub fn create_bom_file() -> Spreadsheet {
     order_sheet.get_cell_mut((4,1)).set_value("Quantity");
     order_sheet.get_cell_mut((5,1)).set_value("Unit price");
     order_sheet.get_cell_mut((6,1)).set_value("Price");
-    order_sheet.get_cell_mut((7,1)).set_value("Price incl. VAT");
+    order_sheet.get_cell_mut((7,1)).set_value(pricing.vat_label());
     order_sheet.get_cell_mut((8,1)).set_value("Proposta (Descrizione spesa)");
     order_sheet.get_cell_mut((9,1)).set_value("Link");
     order_sheet.get_cell_mut((10,1)).set_value("Project");
     order_sheet.get_cell_mut((11,1)).set_value("Delivered");
+    order_sheet.get_cell_mut((12,1)).set_value("Requested quantity");
+    order_sheet.get_cell_mut((13,1)).set_value("Notes");
 
 
     order_sheet.get_cell_mut((5,2)).set_value("Total:");
-    order_sheet.get_cell_mut((6,2)).set_value("0,00");
-    order_sheet.get_cell_mut((7,2)).set_value("0,00");
+    set_amount(order_sheet.get_cell_mut((6,2)), 0.0, pricing);
+    set_amount(order_sheet.get_cell_mut((7,2)), 0.0, pricing);
 
     book
 }
 
-pub fn add_item_to_bom(
+pub fn add_item_to_bom(book: &mut Spreadsheet, row: BomRow, pricing: &PricingSettings) -> Result<(), String> {
+    let order_sheet = book.get_sheet_by_name_mut("Ordine");
+    match order_sheet {
+        Some(order_sheet) => {
+            let row_index = order_sheet.get_highest_row();
+            order_sheet.get_cell_mut((1, row_index)).set_value(row.description);
+            order_sheet.get_cell_mut((2, row_index)).set_value(row.manifacturer_pn);
+            order_sheet.get_cell_mut((3, row_index)).set_value(row.manifacturer);
+            order_sheet.get_cell_mut((4, row_index)).set_value_number(row.quantity);
+            set_amount(order_sheet.get_cell_mut((5, row_index)), row.unit_price, pricing);
+            set_amount_formula(order_sheet.get_cell_mut((6, row_index)), format!("=D{}*E{}", row_index, row_index), pricing);
+            set_amount_formula(order_sheet.get_cell_mut((7, row_index)), format!("=F{}*{}", row_index, pricing.vat_multiplier()), pricing);
+            order_sheet.get_cell_mut((8, row_index)).set_value(row.proposal);
+            order_sheet.get_cell_mut((9, row_index)).set_value(row.link);
+            order_sheet.get_cell_mut((10, row_index)).set_value(row.project);
+            order_sheet.get_cell_mut((11, row_index)).set_value(row.delivered);
+            order_sheet.get_cell_mut((12, row_index)).set_value_number(row.requested_quantity);
+            order_sheet.get_cell_mut((13, row_index)).set_value(row.notes);
+
+            order_sheet.get_cell_mut((5, row_index + 1)).set_value("Total:");
+            set_amount_formula(order_sheet.get_cell_mut((6, row_index + 1)), format!("=SUM(F2:F{})", row_index), pricing);
+            set_amount_formula(order_sheet.get_cell_mut((7, row_index + 1)), format!("=SUM(G2:G{})", row_index), pricing);
+
+            Ok(())
+        }
+        None => {
+            Err("Sheet not found".to_string())
+        }
+    }
+}
+
+/// Adds the shipping and handling rows and the grand total below the items total, once all the items are in the BOM.
+pub fn add_charges_to_bom(
     book: &mut Spreadsheet,
-    manifacturer: String,
-    manifacturer_pn: String,
-    quantity: i32,
-    description: String,
-    unit_price: f64,
-    proposal: String,
-    link: String,
-    project: String,
-    delivered: String,
+    shipping: f64,
+    handling: f64,
+    pricing: &PricingSettings,
 ) -> Result<(), String> {
     let order_sheet = book.get_sheet_by_name_mut("Ordine");
+    match order_sheet {
+        Some(order_sheet) => {
+            let total_row = order_sheet.get_highest_row();
+            let charges = [("Shipping:", shipping), ("Handling:", handling)];
+            for (offset, (label, amount)) in charges.iter().enumerate() {
+                let row_index = total_row + 1 + offset as u32;
+                order_sheet.get_cell_mut((5, row_index)).set_value(*label);
+                set_amount(order_sheet.get_cell_mut((6, row_index)), *amount, pricing);
+                set_amount_formula(order_sheet.get_cell_mut((7, row_index)), format!("=F{}*{}", row_index, pricing.vat_multiplier()), pricing);
+            }
+            let grand_total_row = total_row + 1 + charges.len() as u32;
+            order_sheet.get_cell_mut((5, grand_total_row)).set_value("Grand total:");
+            set_amount_formula(order_sheet.get_cell_mut((6, grand_total_row)), format!("=SUM(F{}:F{})", total_row, grand_total_row - 1), pricing);
+            set_amount_formula(order_sheet.get_cell_mut((7, grand_total_row)), format!("=SUM(G{}:G{})", total_row, grand_total_row - 1), pricing);
+            Ok(())
+        }
+        None => {
+            Err("Sheet not found".to_string())
+        }
+    }
+}
+
+/// BOM of the items bought outside the distributor APIs: one row per item, with the vendor and its shipping cost.
+pub fn create_custom_bom_file(pricing: &PricingSettings) -> Spreadsheet {
+    let mut book: Spreadsheet = umya_spreadsheet::new_file();
+    let _sheet = book.new_sheet("Altri fornitori").unwrap();
+
+    // Header row
+    let order_sheet = book.get_sheet_by_name_mut("Altri fornitori").unwrap();
+
+    order_sheet.get_cell_mut((1,1)).set_value("Vendor");
+    order_sheet.get_cell_mut((2,1)).set_value("Description");
+    order_sheet.get_cell_mut((3,1)).set_value("Quantity");
+    order_sheet.get_cell_mut((4,1)).set_value("Unit price");
+    order_sheet.get_cell_mut((5,1)).set_value("Price");
+    order_sheet.get_cell_mut((6,1)).set_value("Shipping");
+    order_sheet.get_cell_mut((7,1)).set_value(pricing.vat_label());
+    order_sheet.get_cell_mut((8,1)).set_value("Proposta (Descrizione spesa)");
+    order_sheet.get_cell_mut((9,1)).set_value("Link");
+    order_sheet.get_cell_mut((10,1)).set_value("Project");
+    order_sheet.get_cell_mut((11,1)).set_value("Delivered");
+
+    order_sheet.get_cell_mut((4,2)).set_value("Total:");
+    set_amount(order_sheet.get_cell_mut((5,2)), 0.0, pricing);
+    set_amount(order_sheet.get_cell_mut((6,2)), 0.0, pricing);
+    set_amount(order_sheet.get_cell_mut((7,2)), 0.0, pricing);
+
+    book
+}
+
+pub fn add_custom_item_to_bom(
+    book: &mut Spreadsheet,
+    item: &CustomOrderItem,
+    delivered: String,
+    pricing: &PricingSettings,
+) -> Result<(), String> {
+    let order_sheet = book.get_sheet_by_name_mut("Altri fornitori");
     match order_sheet {
         Some(order_sheet) => {
             let row_index = order_sheet.get_highest_row();
-            order_sheet.get_cell_mut((1, row_index)).set_value(description);
-            order_sheet.get_cell_mut((2, row_index)).set_value(manifacturer_pn);
-            order_sheet.get_cell_mut((3, row_index)).set_value(manifacturer);
-            order_sheet.get_cell_mut((4, row_index)).set_value(quantity.to_string());
-            order_sheet.get_cell_mut((5, row_index)).set_value(unit_price.to_string());
-            order_sheet.get_cell_mut((6, row_index)).set_formula(&format!("=D{}*E{}", row_index, row_index));
-            order_sheet.get_cell_mut((7, row_index)).set_formula(&format!("=F{}*1.22", row_index));
-            order_sheet.get_cell_mut((8, row_index)).set_value(proposal);
-            order_sheet.get_cell_mut((9, row_index)).set_value(link);
-            order_sheet.get_cell_mut((10, row_index)).set_value(project);
+            order_sheet.get_cell_mut((1, row_index)).set_value(&item.vendor);
+            order_sheet.get_cell_mut((2, row_index)).set_value(&item.description);
+            order_sheet.get_cell_mut((3, row_index)).set_value_number(item.quantity);
+            set_amount(order_sheet.get_cell_mut((4, row_index)), item.unit_price, pricing);
+            set_amount_formula(order_sheet.get_cell_mut((5, row_index)), format!("=C{}*D{}", row_index, row_index), pricing);
+            set_amount(order_sheet.get_cell_mut((6, row_index)), item.shipping, pricing);
+            set_amount_formula(order_sheet.get_cell_mut((7, row_index)), format!("=(E{}+F{})*{}", row_index, row_index, pricing.vat_multiplier()), pricing);
+            order_sheet.get_cell_mut((8, row_index)).set_value(&item.proposal);
+            order_sheet.get_cell_mut((9, row_index)).set_value(item.url.clone().unwrap_or_default());
+            order_sheet.get_cell_mut((10, row_index)).set_value(&item.project);
             order_sheet.get_cell_mut((11, row_index)).set_value(delivered);
 
-            order_sheet.get_cell_mut((5, row_index + 1)).set_value("Total:");
-            order_sheet.get_cell_mut((6, row_index + 1)).set_formula(&format!("=SUM(F2:F{})", row_index));
-            order_sheet.get_cell_mut((7, row_index + 1)).set_formula(&format!("=SUM(G2:G{})", row_index));
+            order_sheet.get_cell_mut((4, row_index + 1)).set_value("Total:");
+            set_amount_formula(order_sheet.get_cell_mut((5, row_index + 1)), format!("=SUM(E2:E{})", row_index), pricing);
+            set_amount_formula(order_sheet.get_cell_mut((6, row_index + 1)), format!("=SUM(F2:F{})", row_index), pricing);
+            set_amount_formula(order_sheet.get_cell_mut((7, row_index + 1)), format!("=SUM(G2:G{})", row_index), pricing);
 
             Ok(())
         }
@@ -95,6 +211,13 @@ pub fn save_to_file(book: &Spreadsheet) {
 pub fn parse_kicad_bom_file(book: &Spreadsheet) -> Result<Vec<KiCadItem>, String> {
     let sheet = book.get_sheet(&0).expect("Sheet not found");
     let mut items: Vec<KiCadItem> = Vec::new();
+    // reference designators are optional, in the column named like the KiCad "Reference" field
+    let reference_column = (1..=sheet.get_highest_column()).find(|column| {
+        matches!(
+            sheet.get_value((*column, 1)).trim().to_lowercase().as_str(),
+            "reference" | "references" | "designator" | "designators" | "reference designators"
+        )
+    });
 
     for row_num in 2..=sheet.get_highest_row() {
         let quantity = sheet.get_value((1, row_num)).parse::<i32>().unwrap_or(0);
@@ -102,10 +225,14 @@ pub fn parse_kicad_bom_file(book: &Spreadsheet) -> Result<Vec<KiCadItem>, String
         let manifacturer_pn = sheet.get_value((3, row_num));
         if quantity > 0 && !manifacturer.is_empty() && !manifacturer_pn.is_empty() { 
             println!("Adding {} {} {}...", quantity, manifacturer, manifacturer_pn);
+            let reference_designators = reference_column
+                .map(|column| sheet.get_value((column, row_num)).trim().to_string())
+                .filter(|references| !references.is_empty());
             items.push(KiCadItem {
                 quantity,
                 manifacturer,
                 manifacturer_pn,
+                reference_designators,
             });
         }
     }
@@ -0,0 +1,134 @@
+use std::time::Duration;
+use dotenvy::dotenv;
+use crate::models::farnell_api_models::{FarnellResponse, Product};
+use crate::data::{distributor, errors::SourcingError, sourcing::{self, SourcingContext}};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, QuotePriceBreak};
+use serde_path_to_error::deserialize;
+
+/// Farnell store the searches are made in, `FARNELL_STORE` (default it.farnell.com).
+pub fn store() -> String {
+    dotenv().ok();
+    std::env::var("FARNELL_STORE").unwrap_or("it.farnell.com".to_string())
+}
+
+pub async fn search_farnell(
+    sourcing: &SourcingContext,
+    query_manufacturer: &str,
+    query_manufacturer_pn: &str,
+    quantity: u32,
+) -> Result<Option<DistributorQuote>, SourcingError> {
+    search_farnell_term(sourcing, query_manufacturer, query_manufacturer_pn, format!("manuPartNum:{}", query_manufacturer_pn), quantity).await
+}
+
+/// Searches by Farnell order code, for items pinned to a Farnell SKU.
+pub async fn search_farnell_sku(
+    sourcing: &SourcingContext,
+    query_manufacturer: &str,
+    sku: &str,
+    quantity: u32,
+) -> Result<Option<DistributorQuote>, SourcingError> {
+    search_farnell_term(sourcing, query_manufacturer, sku, format!("id:{}", sku), quantity).await
+}
+
+async fn search_farnell_term(
+    sourcing: &SourcingContext,
+    query_manufacturer: &str,
+    query_manufacturer_pn: &str,
+    term: String,
+    quantity: u32,
+) -> Result<Option<DistributorQuote>, SourcingError> {
+    dotenv().ok();
+    let api_key = sourcing::required_env("FARNELL_API_KEY")?;
+    let store = store();
+    println!("Searching for {} {} on Farnell", query_manufacturer, query_manufacturer_pn);
+
+    let _permit = sourcing.acquire(DistributorKind::Farnell).await;
+    let search_response = sourcing.client
+        .get(format!("{}/catalog/products", DistributorKind::Farnell.api_base_url()))
+        .query(&[
+            ("term", term),
+            ("storeInfo.id", store.clone()),
+            ("resultsSettings.offset", "0".to_string()),
+            ("resultsSettings.numberOfResults", "20".to_string()),
+            ("resultsSettings.responseGroup", "large".to_string()),
+            ("callInfo.responseDataFormat", "json".to_string()),
+            ("callInfo.apiKey", api_key),
+        ])
+        .header("accept", "application/json")
+        .timeout(Duration::from_secs(100))
+        .send()
+        .await?;
+
+    if !search_response.status().is_success() {
+        return Err(SourcingError::from_status(search_response.status(), "Failed to search Farnell"));
+    }
+
+    let bytes = search_response.bytes().await?;
+
+    let mut de = serde_json::Deserializer::from_slice(&bytes);
+    let response = match deserialize::<_, FarnellResponse>(&mut de) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("❌ Path error: {}", e);
+            return Err(SourcingError::Parse(e.to_string()));
+        }
+    };
+
+    let products = response
+        .manufacturer_part_number_search_return
+        .or(response.premier_farnell_part_number_return)
+        .and_then(|search_return| search_return.products)
+        .unwrap_or_default();
+
+    if products.is_empty() {
+        return Ok(None);
+    }
+    let similar_parts: Vec<String> = products
+        .iter()
+        .filter_map(|product| product.translated_manufacturer_part_number.clone())
+        .take(5)
+        .collect();
+
+    // assure we return only the requested item, preferring the cheapest one that covers the quantity
+    let quotes: Vec<DistributorQuote> = products
+        .into_iter()
+        .filter(|product| {
+            product.sku == query_manufacturer_pn
+                || product.translated_manufacturer_part_number
+                    .as_deref()
+                    .is_some_and(|pn| pn.eq_ignore_ascii_case(query_manufacturer_pn))
+        })
+        .filter_map(|product| product_to_quote(product, &store, quantity))
+        .collect();
+
+    if quotes.is_empty() {
+        return Err(SourcingError::Ambiguous(similar_parts));
+    }
+
+    Ok(distributor::pick_offer(quotes, quantity))
+}
+
+fn product_to_quote(product: Product, store: &str, quantity: u32) -> Option<DistributorQuote> {
+    let price_breaks: Vec<QuotePriceBreak> = product.prices?
+        .iter()
+        .map(|price| QuotePriceBreak {
+            quantity: price.from,
+            unit_price: price.cost,
+        })
+        .collect();
+
+    let mut quote = DistributorQuote {
+        distributor: DistributorKind::Farnell,
+        manufacturer: product.brand_name.or(product.vendor_name).unwrap_or_default(),
+        manufacturer_pn: product.translated_manufacturer_part_number.unwrap_or_default(),
+        description: product.display_name.unwrap_or_default(),
+        product_url: format!("https://{}/search?st={}", store, product.sku),
+        distributor_pn: product.sku,
+        unit_price: 0.0,
+        availability: product.stock.map(|stock| stock.level).unwrap_or_default(),
+        minimum_order_quantity: product.translated_minimum_order_quality.unwrap_or(1),
+        price_breaks,
+    };
+    quote.unit_price = quote.unit_price_for(quantity).unwrap_or(0.0);
+    Some(quote)
+}
@@ -0,0 +1,138 @@
+use sqlx::{PgConnection, PgPool};
+use crate::data::errors::DataError;
+use crate::models::inventory::{InventoryAdjustment, InventoryItem, InventoryMovement, InventoryStock};
+
+/// Where parts are stored when no location is given, e.g. when an order is received: `INVENTORY_DEFAULT_LOCATION` (default "Incoming").
+pub fn default_location() -> String {
+    std::env::var("INVENTORY_DEFAULT_LOCATION").unwrap_or("Incoming".to_string())
+}
+
+/// Applies the adjustment (parts added, or taken when the change is negative) and records the movement.
+/// Returns the updated inventory row; the quantity can never go below zero.
+/// Takes a connection so that it can be part of a larger transaction, like the recording of a delivery.
+pub async fn adjust_stock(
+    conn: &mut PgConnection,
+    adjustment: &InventoryAdjustment,
+    order_id: Option<i32>,
+    user_id: Option<i32>,
+) -> Result<InventoryItem, DataError> {
+    let manufacturer = adjustment.manufacturer.trim();
+    let manufacturer_pn = adjustment.manufacturer_pn.trim();
+    let location = match adjustment.location.trim() {
+        "" => default_location(),
+        location => location.to_string(),
+    };
+    let reason = match adjustment.reason.trim() {
+        "" => "Adjusted by hand",
+        reason => reason,
+    };
+    let change = adjustment.change;
+    if manufacturer_pn.is_empty() || change == 0 {
+        return Err(DataError::Internal("An inventory change needs a part number and a quantity".to_string()));
+    }
+    let on_hand = get_stock_at(&mut *conn, manufacturer, manufacturer_pn, &location).await?;
+    if on_hand + change < 0 {
+        return Err(DataError::Internal(format!(
+            "Only {} of {} {} in {}, cannot take {}",
+            on_hand, manufacturer, manufacturer_pn, location, -change
+        )));
+    }
+
+    // the quantity and its movement are written by the same statement, a new row never starts below zero
+    // parts differing only by case share the row, which keeps the spelling it was created with
+    let item = sqlx::query_as!(
+        InventoryItem,
+        r#"WITH item AS (
+            INSERT INTO inventory_items (manufacturer, manufacturer_pn, location, quantity) VALUES ($1, $2, $3, GREATEST($4, 0))
+            ON CONFLICT (LOWER(manufacturer), LOWER(manufacturer_pn), location)
+            DO UPDATE SET quantity = inventory_items.quantity + $4, updated_at = NOW()
+            RETURNING *
+         ), movement AS (
+            INSERT INTO inventory_movements (inventory_item_id, change, reason, order_id, user_id)
+            SELECT id, $4, $5, $6, $7 FROM item
+         )
+         SELECT id as "id!", manufacturer as "manufacturer!", manufacturer_pn as "manufacturer_pn!", location as "location!",
+            quantity as "quantity!", updated_at as "updated_at!: chrono::DateTime<chrono::Utc>"
+         FROM item"#,
+        manufacturer,
+        manufacturer_pn,
+        location,
+        change,
+        reason,
+        order_id,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(item)
+}
+
+async fn get_stock_at(conn: &mut PgConnection, manufacturer: &str, manufacturer_pn: &str, location: &str) -> Result<i32, DataError> {
+    let quantity = sqlx::query_scalar!(
+        "SELECT quantity FROM inventory_items WHERE LOWER(manufacturer) = LOWER($1) AND LOWER(manufacturer_pn) = LOWER($2) AND location = $3",
+        manufacturer,
+        manufacturer_pn,
+        location
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(quantity.unwrap_or(0))
+}
+
+/// The whole inventory, or the rows whose manufacturer, part number or location contain `search`.
+pub async fn search_inventory(pool: &PgPool, search: &str) -> Result<Vec<InventoryItem>, DataError> {
+    let items = sqlx::query_as!(
+        InventoryItem,
+        r#"SELECT id, manufacturer, manufacturer_pn, location, quantity,
+            updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+         FROM inventory_items
+         WHERE $1 = '' OR manufacturer ILIKE '%' || $1 || '%' OR manufacturer_pn ILIKE '%' || $1 || '%' OR location ILIKE '%' || $1 || '%'
+         ORDER BY manufacturer, manufacturer_pn, location"#,
+        search.trim()
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(items)
+}
+
+pub async fn get_recent_movements(pool: &PgPool, limit: i64) -> Result<Vec<InventoryMovement>, DataError> {
+    let movements = sqlx::query_as!(
+        InventoryMovement,
+        r#"SELECT m.id, i.manufacturer, i.manufacturer_pn, i.location, m.change, m.reason, m.order_id,
+            u.username as "username?",
+            m.created_at as "created_at: chrono::DateTime<chrono::Utc>"
+         FROM inventory_movements m
+         JOIN inventory_items i ON i.id = m.inventory_item_id
+         LEFT JOIN users u ON u.id = m.user_id
+         ORDER BY m.created_at DESC, m.id DESC
+         LIMIT $1"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(movements)
+}
+
+/// Items of the order that the lab already has in stock, so that they are not bought again.
+pub async fn get_stock_for_order(pool: &PgPool, order_id: i32) -> Result<Vec<InventoryStock>, DataError> {
+    let stock = sqlx::query_as!(
+        InventoryStock,
+        r#"SELECT o.manufacturer, o.manufacturer_pn, o.quantity AS required,
+            SUM(i.quantity)::INT AS "on_hand!",
+            STRING_AGG(i.location || ' (' || i.quantity || ')', ', ' ORDER BY i.location) AS "locations!"
+         FROM order_items o
+         JOIN inventory_items i ON LOWER(i.manufacturer_pn) = LOWER(o.manufacturer_pn) AND LOWER(i.manufacturer) = LOWER(o.manufacturer)
+         WHERE o.order_id = $1 AND i.quantity > 0
+         GROUP BY o.manufacturer, o.manufacturer_pn, o.quantity
+         ORDER BY o.manufacturer, o.manufacturer_pn"#,
+        order_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(stock)
+}
@@ -1,6 +1,7 @@
-use crate::data::errors::DataError;
+use crate::data::errors::{DataError, SourcingError};
 use sqlx::PgPool;
-use crate::models::item::OrderItem;
+use crate::models::item::{BomExportRow, CustomOrderItem, ItemAllocation, ItemSourcingResult, OrderItem, SourcingOverviewRow};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, Offer};
 
 pub async fn get_items_from_order(order_id: i32, pool: &PgPool) -> Result<Vec<OrderItem>, DataError> {
     let user_orders = sqlx::query_as!(
@@ -14,21 +15,223 @@ pub async fn get_items_from_order(order_id: i32, pool: &PgPool) -> Result<Vec<Or
     Ok(user_orders)
 }
 
+pub async fn get_item(pool: &PgPool, order_id: i32, manufacturer: &str, manufacturer_pn: &str) -> Result<Option<OrderItem>, DataError> {
+    let item = sqlx::query_as!(
+        OrderItem,
+        "SELECT * FROM order_items WHERE order_id = $1 AND manufacturer = $2 AND manufacturer_pn = $3",
+        order_id,
+        manufacturer,
+        manufacturer_pn
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(item)
+}
+
+/// Pins an item to a distributor, or clears the pin with `None`. A pinned part number is only kept together with a distributor.
+pub async fn set_item_distributor_preference(
+    pool: &PgPool,
+    order_id: i32,
+    manufacturer: String,
+    manufacturer_pn: String,
+    preferred_distributor: Option<DistributorKind>,
+    pinned_distributor_pn: Option<String>,
+) -> Result<(), DataError> {
+    let pinned_distributor_pn = match preferred_distributor {
+        Some(_) => pinned_distributor_pn.filter(|pn| !pn.trim().is_empty()),
+        None => None,
+    };
+    sqlx::query!(
+        "UPDATE order_items SET preferred_distributor = $1, pinned_distributor_pn = $2,
+            sourcing_outdated = sourcing_outdated OR preferred_distributor IS DISTINCT FROM $1 OR pinned_distributor_pn IS DISTINCT FROM $2
+         WHERE order_id = $3 AND manufacturer = $4 AND manufacturer_pn = $5",
+        preferred_distributor.map(|kind| kind.name()),
+        pinned_distributor_pn,
+        order_id,
+        manufacturer,
+        manufacturer_pn
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+/// Appends reference designators to an item, the same part may be on several lines of a KiCad BOM.
+pub async fn add_item_reference_designators(
+    pool: &PgPool,
+    order_id: i32,
+    manufacturer: &str,
+    manufacturer_pn: &str,
+    reference_designators: &str,
+) -> Result<(), DataError> {
+    sqlx::query!(
+        "UPDATE order_items SET reference_designators = CASE
+            WHEN reference_designators IS NULL OR reference_designators = '' THEN $4
+            ELSE reference_designators || ', ' || $4
+         END
+         WHERE order_id = $1 AND manufacturer = $2 AND manufacturer_pn = $3",
+        order_id,
+        manufacturer,
+        manufacturer_pn,
+        reference_designators
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+/// What has to be bought from a distributor, with the project and reference designators of each item.
+pub async fn get_bom_export_rows(
+    order_id: i32,
+    distributor: DistributorKind,
+    pool: &PgPool,
+) -> Result<Vec<BomExportRow>, DataError> {
+    let rows = sqlx::query_as!(
+        BomExportRow,
+        "SELECT a.manufacturer, a.manufacturer_pn, a.distributor_pn, a.quantity, i.project, i.reference_designators
+         FROM order_item_allocations a
+         JOIN order_items i ON i.order_id = a.order_id AND i.manufacturer = a.manufacturer AND i.manufacturer_pn = a.manufacturer_pn
+         WHERE a.order_id = $1 AND a.distributor = $2
+         ORDER BY a.manufacturer, a.manufacturer_pn",
+        order_id,
+        distributor.name()
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(rows)
+}
+
 pub async fn set_item_pn(
     pool: &PgPool, 
     order_id: i32, 
     manufacturer: String, 
     manufacturer_pn: String,
-    mouser_pn: Option<String>,
-    digikey_pn: Option<String>
+    offers: &[Offer],
 ) -> Result<(), DataError> {
-    
+    let distributor_pn = |kind: DistributorKind| offers
+        .iter()
+        .find(|offer| offer.quote.distributor == kind)
+        .map(|offer| offer.quote.distributor_pn.clone());
+    let purchase_quantity = match offers.is_empty() {
+        true => None,
+        false => Some(offers.iter().map(|offer| offer.quantity as i32).sum::<i32>()),
+    };
+
     sqlx::query!(
         "UPDATE order_items 
-         SET mouser_pn = $1, digikey_pn = $2 
-         WHERE order_id = $3 AND manufacturer = $4 AND manufacturer_pn = $5",
-        mouser_pn,
-        digikey_pn,
+         SET mouser_pn = $1, digikey_pn = $2, farnell_pn = $3, lcsc_pn = $4, tme_pn = $5, purchase_quantity = $6 
+         WHERE order_id = $7 AND manufacturer = $8 AND manufacturer_pn = $9",
+        distributor_pn(DistributorKind::Mouser),
+        distributor_pn(DistributorKind::DigiKey),
+        distributor_pn(DistributorKind::Farnell),
+        distributor_pn(DistributorKind::Lcsc),
+        distributor_pn(DistributorKind::Tme),
+        purchase_quantity,
+        order_id,
+        manufacturer,
+        manufacturer_pn
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+
+    Ok(())
+}
+
+pub async fn clear_item_allocations(pool: &PgPool, order_id: i32) -> Result<(), DataError> {
+    sqlx::query!(
+        "DELETE FROM order_item_allocations WHERE order_id = $1",
+        order_id
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+pub async fn add_item_allocation(
+    pool: &PgPool,
+    order_id: i32,
+    manufacturer: String,
+    manufacturer_pn: String,
+    offer: &Offer,
+) -> Result<(), DataError> {
+    sqlx::query!(
+        "INSERT INTO order_item_allocations (order_id, manufacturer, manufacturer_pn, distributor, distributor_pn, quantity, unit_price)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         ON CONFLICT (order_id, manufacturer, manufacturer_pn, distributor)
+         DO UPDATE SET distributor_pn = EXCLUDED.distributor_pn, quantity = EXCLUDED.quantity, unit_price = EXCLUDED.unit_price",
+        order_id,
+        manufacturer,
+        manufacturer_pn,
+        offer.quote.distributor.name(),
+        offer.quote.distributor_pn,
+        offer.quantity as i32,
+        offer.unit_price
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+pub async fn get_allocations_for_distributor(
+    order_id: i32,
+    distributor: DistributorKind,
+    pool: &PgPool,
+) -> Result<Vec<ItemAllocation>, DataError> {
+    let allocations = sqlx::query_as!(
+        ItemAllocation,
+        "SELECT * FROM order_item_allocations WHERE order_id = $1 AND distributor = $2",
+        order_id,
+        distributor.name()
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(allocations)
+}
+
+
+/// Whether items were added or changed since the last BOM generation, so the stored offers do not price them.
+pub async fn has_outdated_sourcing(pool: &PgPool, order_id: i32) -> Result<bool, DataError> {
+    let outdated = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM order_items WHERE order_id = $1 AND sourcing_outdated) AS "outdated!""#,
+        order_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(outdated)
+}
+
+pub async fn get_allocations_from_order(order_id: i32, pool: &PgPool) -> Result<Vec<ItemAllocation>, DataError> {
+    let allocations = sqlx::query_as!(
+        ItemAllocation,
+        "SELECT * FROM order_item_allocations WHERE order_id = $1",
+        order_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(allocations)
+}
+
+/// Stores the notes of the item's sourcing, which is then up to date with the item.
+pub async fn set_item_sourcing_notes(
+    pool: &PgPool,
+    order_id: i32,
+    manufacturer: String,
+    manufacturer_pn: String,
+    notes: Option<String>,
+) -> Result<(), DataError> {
+    sqlx::query!(
+        "UPDATE order_items SET sourcing_notes = $1, sourcing_outdated = FALSE WHERE order_id = $2 AND manufacturer = $3 AND manufacturer_pn = $4",
+        notes,
         order_id,
         manufacturer,
         manufacturer_pn
@@ -36,6 +239,192 @@ pub async fn set_item_pn(
     .execute(pool)
     .await
     .map_err(DataError::Query)?;
+    Ok(())
+}
+
+pub async fn clear_item_sourcing_results(pool: &PgPool, order_id: i32) -> Result<(), DataError> {
+    sqlx::query!(
+        "DELETE FROM item_sourcing_results WHERE order_id = $1",
+        order_id
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
 
+/// Records what `distributor` answered for an item: its quote, or why there is none.
+pub async fn add_item_sourcing_result(
+    pool: &PgPool,
+    order_id: i32,
+    manufacturer: String,
+    manufacturer_pn: String,
+    distributor: DistributorKind,
+    result: Result<&DistributorQuote, &SourcingError>,
+) -> Result<(), DataError> {
+    let (quote, error) = match result {
+        Ok(quote) => (Some(quote), None),
+        Err(e) => (None, Some(e)),
+    };
+    let price_breaks = match quote {
+        Some(quote) => Some(serde_json::to_string(&quote.price_breaks).map_err(|e| DataError::Internal(e.to_string()))?),
+        None => None,
+    };
+    sqlx::query!(
+        "INSERT INTO item_sourcing_results (order_id, manufacturer, manufacturer_pn, distributor, distributor_pn, quoted_manufacturer, quoted_manufacturer_pn,
+            description, product_url, unit_price, availability, minimum_order_quantity, price_breaks, error, error_message, searched_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, NOW())
+         ON CONFLICT (order_id, manufacturer, manufacturer_pn, distributor)
+         DO UPDATE SET distributor_pn = EXCLUDED.distributor_pn, quoted_manufacturer = EXCLUDED.quoted_manufacturer,
+            quoted_manufacturer_pn = EXCLUDED.quoted_manufacturer_pn, description = EXCLUDED.description, product_url = EXCLUDED.product_url,
+            unit_price = EXCLUDED.unit_price, availability = EXCLUDED.availability, minimum_order_quantity = EXCLUDED.minimum_order_quantity,
+            price_breaks = EXCLUDED.price_breaks, error = EXCLUDED.error, error_message = EXCLUDED.error_message, searched_at = EXCLUDED.searched_at",
+        order_id,
+        manufacturer,
+        manufacturer_pn,
+        distributor.name(),
+        quote.map(|q| q.distributor_pn.clone()),
+        quote.map(|q| q.manufacturer.clone()),
+        quote.map(|q| q.manufacturer_pn.clone()),
+        quote.map(|q| q.description.clone()),
+        quote.map(|q| q.product_url.clone()),
+        quote.map(|q| q.unit_price),
+        quote.map(|q| q.availability.min(i32::MAX as u32) as i32),
+        quote.map(|q| q.minimum_order_quantity.min(i32::MAX as u32) as i32),
+        price_breaks,
+        error.map(|e| e.kind().to_string()),
+        error.map(|e| e.to_string())
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+pub async fn get_sourcing_results(order_id: i32, pool: &PgPool) -> Result<Vec<ItemSourcingResult>, DataError> {
+    let results = sqlx::query_as!(
+        ItemSourcingResult,
+        "SELECT order_id, manufacturer, manufacturer_pn, distributor, distributor_pn, quoted_manufacturer, quoted_manufacturer_pn,
+            description, product_url, unit_price, availability, minimum_order_quantity, price_breaks, error, error_message
+         FROM item_sourcing_results WHERE order_id = $1",
+        order_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(results)
+}
+
+/// Chosen quotes of every item of the order, with unit and extended price and the stock seen at the last BOM generation.
+pub async fn get_sourcing_overview(order_id: i32, pool: &PgPool) -> Result<Vec<SourcingOverviewRow>, DataError> {
+    let items = get_items_from_order(order_id, pool).await?;
+    let allocations = get_allocations_from_order(order_id, pool).await?;
+    let results = get_sourcing_results(order_id, pool).await?;
+
+    let mut rows: Vec<SourcingOverviewRow> = Vec::new();
+    for item in items {
+        let same_item = |manufacturer: &str, manufacturer_pn: &str| manufacturer == item.manufacturer && manufacturer_pn == item.manufacturer_pn;
+        let item_results: Vec<&ItemSourcingResult> = results
+            .iter()
+            .filter(|r| same_item(&r.manufacturer, &r.manufacturer_pn))
+            .collect();
+        if item_results.is_empty() {
+            continue; // not sourced yet
+        }
+        let item_allocations: Vec<&ItemAllocation> = allocations
+            .iter()
+            .filter(|a| same_item(&a.manufacturer, &a.manufacturer_pn))
+            .collect();
+
+        if item_allocations.is_empty() {
+            rows.push(SourcingOverviewRow {
+                manufacturer: item.manufacturer.clone(),
+                manufacturer_pn: item.manufacturer_pn.clone(),
+                requested_quantity: item.quantity,
+                distributor: None,
+                distributor_pn: None,
+                quantity: 0,
+                unit_price: 0.0,
+                extended_price: 0.0,
+                availability: None,
+                product_url: None,
+                notes: item.sourcing_notes.clone().unwrap_or_default(),
+            });
+            continue;
+        }
+        for allocation in item_allocations {
+            let result = item_results.iter().find(|r| r.distributor == allocation.distributor);
+            rows.push(SourcingOverviewRow {
+                manufacturer: item.manufacturer.clone(),
+                manufacturer_pn: item.manufacturer_pn.clone(),
+                requested_quantity: item.quantity,
+                distributor: Some(allocation.distributor.clone()),
+                distributor_pn: Some(allocation.distributor_pn.clone()),
+                quantity: allocation.quantity,
+                unit_price: allocation.unit_price,
+                extended_price: allocation.quantity as f64 * allocation.unit_price,
+                availability: result.and_then(|r| r.availability),
+                product_url: result.and_then(|r| r.product_url.clone()),
+                notes: item.sourcing_notes.clone().unwrap_or_default(),
+            });
+        }
+    }
+    Ok(rows)
+}
+
+pub async fn get_custom_items_from_order(order_id: i32, pool: &PgPool) -> Result<Vec<CustomOrderItem>, DataError> {
+    let items = sqlx::query_as!(
+        CustomOrderItem,
+        "SELECT * FROM custom_order_items WHERE order_id = $1 ORDER BY vendor, id",
+        order_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(items)
+}
+
+/// An item bought outside the supported distributors, as entered on the edit order page.
+#[derive(Debug, Clone)]
+pub struct NewCustomItem {
+    pub vendor: String,
+    pub description: String,
+    pub url: Option<String>,
+    pub quantity: i32,
+    pub unit_price: f64,
+    pub shipping: f64,
+    pub proposal: String,
+    pub project: String,
+}
+
+pub async fn add_custom_item(pool: &PgPool, order_id: i32, item: &NewCustomItem) -> Result<(), DataError> {
+    sqlx::query!(
+        "INSERT INTO custom_order_items (order_id, vendor, description, url, quantity, unit_price, shipping, proposal, project)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        order_id,
+        item.vendor,
+        item.description,
+        item.url,
+        item.quantity,
+        item.unit_price,
+        item.shipping,
+        item.proposal,
+        item.project
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+pub async fn delete_custom_item(pool: &PgPool, order_id: i32, custom_item_id: i32) -> Result<(), DataError> {
+    sqlx::query!(
+        "DELETE FROM custom_order_items WHERE id = $1 AND order_id = $2",
+        custom_item_id,
+        order_id
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! LCSC client. LCSC has no public API: the searches go to the undocumented endpoints behind its website
+//! (`/ftps/wm/search/global` and `/ftps/wm/product/detail`), with the currency picked by the `currencyCode` cookie.
+//! They can change without notice, a failing LCSC search does not stop the other distributors.
+
+use std::time::Duration;
+use dotenvy::dotenv;
+use crate::models::lcsc_api_models::{LcscDetailResponse, LcscSearchResponse, Product};
+use crate::data::{distributor, errors::SourcingError, sourcing::SourcingContext};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, QuotePriceBreak};
+use serde_path_to_error::deserialize;
+
+/// Currency of the prices, `LCSC_CURRENCY` overrides the pricing settings.
+pub fn currency(sourcing: &SourcingContext) -> String {
+    dotenv().ok();
+    std::env::var("LCSC_CURRENCY").unwrap_or(sourcing.pricing.currency.clone())
+}
+
+pub async fn search_lcsc(
+    sourcing: &SourcingContext,
+    query_manufacturer: &str,
+    query_manufacturer_pn: &str,
+    quantity: u32,
+) -> Result<Option<DistributorQuote>, SourcingError> {
+    dotenv().ok();
+    let currency = currency(sourcing);
+    println!("Searching for {} {} on LCSC", query_manufacturer, query_manufacturer_pn);
+
+    let permit = sourcing.acquire(DistributorKind::Lcsc).await;
+    let search_response = sourcing.client
+        .get(format!("{}/ftps/wm/search/global", DistributorKind::Lcsc.api_base_url()))
+        .query(&[("keyword", query_manufacturer_pn)])
+        .header("accept", "application/json")
+        .header("Cookie", format!("currencyCode={}", currency))
+        .timeout(Duration::from_secs(100))
+        .send()
+        .await?;
+
+    if !search_response.status().is_success() {
+        return Err(SourcingError::from_status(search_response.status(), "Failed to search LCSC"));
+    }
+
+    let bytes = search_response.bytes().await?;
+    drop(permit);
+
+    let mut de = serde_json::Deserializer::from_slice(&bytes);
+    let response = match deserialize::<_, LcscSearchResponse>(&mut de) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("❌ Path error: {}", e);
+            return Err(SourcingError::Parse(e.to_string()));
+        }
+    };
+
+    let result = match response.result {
+        Some(result) => result,
+        None => return Ok(None),
+    };
+
+    let products: Vec<Product> = match (result.product_search_result, result.tip_product_detail) {
+        (Some(search_result), _) => search_result.product_list.unwrap_or_default(),
+        // an exact match redirects to the product page, fetch its details instead
+        (None, Some(tip)) => get_lcsc_product(sourcing, &tip.product_code, &currency)
+            .await?
+            .into_iter()
+            .collect(),
+        (None, None) => Vec::new(),
+    };
+
+    if products.is_empty() {
+        return Ok(None);
+    }
+    let similar_parts: Vec<String> = products
+        .iter()
+        .filter_map(|product| product.product_model.clone())
+        .take(5)
+        .collect();
+
+    // assure we return only the requested item, preferring the cheapest one that covers the quantity
+    let quotes: Vec<DistributorQuote> = products
+        .into_iter()
+        .filter(|product| {
+            product.product_code == query_manufacturer_pn
+                || product.product_model
+                    .as_deref()
+                    .is_some_and(|pn| pn.eq_ignore_ascii_case(query_manufacturer_pn))
+        })
+        .map(|product| product_to_quote(product, quantity, &currency))
+        .collect::<Result<Vec<Option<DistributorQuote>>, SourcingError>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if quotes.is_empty() {
+        return Err(SourcingError::Ambiguous(similar_parts));
+    }
+
+    Ok(distributor::pick_offer(quotes, quantity))
+}
+
+async fn get_lcsc_product(
+    sourcing: &SourcingContext,
+    product_code: &str,
+    currency: &str,
+) -> Result<Option<Product>, SourcingError> {
+    let _permit = sourcing.acquire(DistributorKind::Lcsc).await;
+    let detail_response = sourcing.client
+        .get(format!("{}/ftps/wm/product/detail", DistributorKind::Lcsc.api_base_url()))
+        .query(&[("productCode", product_code)])
+        .header("accept", "application/json")
+        .header("Cookie", format!("currencyCode={}", currency))
+        .timeout(Duration::from_secs(100))
+        .send()
+        .await?;
+
+    if !detail_response.status().is_success() {
+        return Err(SourcingError::from_status(detail_response.status(), &format!("Failed to get LCSC product {}", product_code)));
+    }
+
+    let bytes = detail_response.bytes().await?;
+    let mut de = serde_json::Deserializer::from_slice(&bytes);
+    match deserialize::<_, LcscDetailResponse>(&mut de) {
+        Ok(result) => Ok(result.result),
+        Err(e) => {
+            eprintln!("❌ Path error: {}", e);
+            Err(SourcingError::Parse(e.to_string()))
+        }
+    }
+}
+
+/// Only prices in the requested currency are used: USD prices cannot be compared with the other distributors.
+fn product_to_quote(product: Product, quantity: u32, currency: &str) -> Result<Option<DistributorQuote>, SourcingError> {
+    let price_list = match product.product_price_list {
+        Some(price_list) => price_list,
+        None => return Ok(None),
+    };
+    let price_breaks: Vec<QuotePriceBreak> = price_list
+        .iter()
+        .filter_map(|price| Some(QuotePriceBreak {
+            quantity: price.ladder,
+            unit_price: price.currency_price?,
+        }))
+        .collect();
+    if price_breaks.is_empty() && !price_list.is_empty() {
+        // asking again gives the same answer: the currency has to be changed
+        return Err(SourcingError::Config(format!("LCSC has no {} price for {}, set LCSC_CURRENCY to a currency it sells in", currency, product.product_code)));
+    }
+
+    let mut quote = DistributorQuote {
+        distributor: DistributorKind::Lcsc,
+        manufacturer: product.brand_name_en.unwrap_or_default(),
+        manufacturer_pn: product.product_model.unwrap_or_default(),
+        description: product.product_intro_en.unwrap_or_default(),
+        product_url: format!("https://www.lcsc.com/product-detail/{}.html", product.product_code),
+        distributor_pn: product.product_code,
+        unit_price: 0.0,
+        availability: product.stock_number.unwrap_or_default(),
+        minimum_order_quantity: product.min_buy_number.unwrap_or(1),
+        price_breaks,
+    };
+    quote.unit_price = quote.unit_price_for(quantity).unwrap_or(0.0);
+    Ok(Some(quote))
+}
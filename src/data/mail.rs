@@ -0,0 +1,46 @@
+use dotenvy::dotenv;
+use lettre::{message::{header, MultiPart, SinglePart}, transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use crate::data::errors::DataError;
+
+const SMTP_SERVER: &str = "smtp.gmail.com";
+
+fn smtp_credentials() -> Result<(String, String), DataError> {
+    dotenv().ok();
+    let smtp_user = std::env::var("SMTP_USER")
+        .map_err(|_| DataError::Mail("SMTP_USER not set in .env".to_string()))?;
+    let smtp_pass = std::env::var("SMTP_PASS")
+        .map_err(|_| DataError::Mail("SMTP_PASS not set in .env".to_string()))?;
+    Ok((smtp_user, smtp_pass))
+}
+
+/// Sends an email from the `SMTP_USER` account.
+pub async fn send_mail(recipient: &str, subject: &str, body: MultiPart) -> Result<(), DataError> {
+    let (smtp_user, smtp_pass) = smtp_credentials()?;
+
+    let email = Message::builder()
+        .from(smtp_user.parse().map_err(|e: lettre::address::AddressError| DataError::Mail(e.to_string()))?)
+        .to(recipient.parse().map_err(|e: lettre::address::AddressError| DataError::Mail(e.to_string()))?)
+        .subject(subject)
+        .multipart(body)
+        .map_err(|e| DataError::Mail(e.to_string()))?;
+    println!("verifying credentials...");
+    let creds = Credentials::new(smtp_user, smtp_pass);
+    let mailer = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(SMTP_SERVER)
+        .map_err(|e| DataError::Mail(e.to_string()))?
+        .credentials(creds)
+        .build();
+
+    mailer.send(email).await.map_err(|e| DataError::Mail(e.to_string()))?;
+    println!("Email sent successfully!");
+    Ok(())
+}
+
+/// Sends a plain text email.
+pub async fn send_text_mail(recipient: &str, subject: &str, text: String) -> Result<(), DataError> {
+    let text_ct: header::ContentType = "text/plain; charset=utf-8"
+        .parse()
+        .map_err(|e: header::ContentTypeErr| DataError::Mail(e.to_string()))?;
+    let body = MultiPart::mixed()
+        .singlepart(SinglePart::builder().header(text_ct).body(text));
+    send_mail(recipient, subject, body).await
+}
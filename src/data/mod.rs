@@ -4,4 +4,19 @@ pub mod order;
 pub mod item;
 pub mod excel;
 pub mod mouser_apis;
-pub mod digikey_apis;
\ No newline at end of file
+pub mod digikey_apis;
+pub mod farnell_apis;
+pub mod lcsc_apis;
+pub mod tme_apis;
+pub mod distributor;
+pub mod distributor_cache;
+pub mod csv_export;
+pub mod optimiser;
+pub mod sourcing;
+pub mod price_history;
+pub mod mail;
+pub mod notification;
+pub mod stock_watch;
+pub mod budget;
+pub mod receiving;
+pub mod inventory;
@@ -1,25 +1,28 @@
 use std::{time::Duration};
-use dotenvy::dotenv;
-use reqwest::{Client, Response};
 use tokio::time::sleep;
 use crate::models::mouser_api_models::{
-    MouserPart,
+    CartInsertRequest,
+    CartItem,
+    CartResponse,
     KeywordSearchRequest,
     InnerRequest,
     MouserResponse,
 };
+use crate::data::{errors::SourcingError, sourcing::{self, SourcingContext}};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, QuotePriceBreak};
 use serde_path_to_error::deserialize;
 
 pub async fn search_mouser(
+    sourcing: &SourcingContext,
     query_manufacturer: &str,
     query_manufacturer_pn: &str,
     quantity: u32,
-) -> Result<Option<MouserPart>, Box<dyn std::error::Error + Send + Sync>> {
-    dotenv().ok();
-    let api_key = std::env::var("MOUSER_API_KEY").expect("MOUSER_API_KEY must be set");
+) -> Result<Option<DistributorQuote>, SourcingError> {
+    let api_key = sourcing::required_env("MOUSER_API_KEY")?;
     println!("Searching for {} {} on Mouser", query_manufacturer, query_manufacturer_pn);
     let url = format!(
-        "https://api.mouser.com/api/v1/search/keyword?apiKey={}",
+        "{}/api/v1/search/keyword?apiKey={}",
+        DistributorKind::Mouser.api_base_url(),
         api_key
     );
     let request_body = KeywordSearchRequest {
@@ -31,109 +34,223 @@ pub async fn search_mouser(
             search_with_your_sign_up_language: "".into(),
         },
     };
-    let client = Client::new();
-    let mut search_response: Response;
 
     let mut attempts = 0;
-    let max_attempts = 16;
-    loop {
-        search_response = client
-        .post(&url)
+    let max_attempts = 5;
+    let response: MouserResponse = loop {
+        let result = mouser_keyword_search(sourcing, &url, &request_body).await;
+        match result {
+            Ok(response) => break response,
+            Err(e) if e.is_retryable() && attempts < max_attempts => {
+                attempts += 1;
+                println!("Mouser search failed ({}), retrying, attempt {}", e, attempts);
+                // requests are already rate limited, back off exponentially only when mouser still refuses them
+                sleep(Duration::from_millis(250 * 2u64.pow(attempts))).await;
+            },
+            Err(e) => return Err(e),
+        }
+    };
+
+    // mouser reports some failures, e.g. a wrong API key, with a successful status code
+    if let Some(message) = response.errors
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|error| error.message)
+        .next()
+    {
+        let lowercase = message.to_lowercase();
+        return Err(if lowercase.contains("invalid unique identifier") || lowercase.contains("api key") {
+            SourcingError::Auth(message)
+        } else if lowercase.contains("exceed") || lowercase.contains("too many") {
+            SourcingError::Quota(message)
+        } else {
+            SourcingError::Http(message)
+        });
+    }
+
+    let parts = match response.search_results {
+        Some(search_results) => search_results.parts,
+        None => return Ok(None),
+    };
+    if parts.is_empty() {
+        return Ok(None);
+    }
+
+    let mut similar_parts: Vec<String> = Vec::new();
+    for part in parts {
+        let manufacturer = part.manufacturer.unwrap_or_default();
+        let manufacturer_pn = part.manufacturer_part_number.unwrap_or_default();
+        let mouser_pn = part.mouser_part_number.unwrap_or_default();
+        // assure we return only the requested item
+        if manufacturer_pn != query_manufacturer_pn && mouser_pn != query_manufacturer_pn {
+            if !manufacturer_pn.is_empty() && !similar_parts.contains(&manufacturer_pn) {
+                similar_parts.push(manufacturer_pn);
+            }
+            continue;
+        }
+        let price_breaks: Vec<QuotePriceBreak> = match part.price_breaks {
+            Some(price_breaks) => price_breaks
+                .iter()
+                .map(|price| QuotePriceBreak {
+                    quantity: price.Quantity,
+                    unit_price: parse_mouser_price(&price.Price),
+                })
+                .collect(),
+            None => {
+                return Ok(None);
+            }
+        };
+        let mut mouser_part = DistributorQuote {
+            distributor: DistributorKind::Mouser,
+            manufacturer,
+            manufacturer_pn,
+            description: part.description.unwrap_or_default(),
+            distributor_pn: mouser_pn,
+            product_url: part.product_detail_url.unwrap_or_default(),
+            unit_price: 0.0,
+            availability: part.availability
+                .clone()
+                .unwrap_or_default()
+                .strip_suffix(" In Stock")
+                .unwrap_or_default()
+                .parse::<u32>()
+                .unwrap_or_default(),
+            minimum_order_quantity: part.min
+                .unwrap_or_default()
+                .parse::<u32>()
+                .unwrap_or(1),
+            price_breaks,
+        };
+        mouser_part.unit_price = mouser_part.unit_price_for(quantity).unwrap_or(0.0);
+        return Ok(Some(mouser_part));
+    }
+
+    similar_parts.truncate(5);
+    Err(SourcingError::Ambiguous(similar_parts))
+}
+
+async fn mouser_keyword_search(
+    sourcing: &SourcingContext,
+    url: &str,
+    request_body: &KeywordSearchRequest,
+) -> Result<MouserResponse, SourcingError> {
+    let _permit = sourcing.acquire(DistributorKind::Mouser).await;
+    let search_response = sourcing.client
+        .post(url)
         .header("accept", "application/json")
         .header("Content-Type", "application/json")
-        .json(&request_body)
+        .json(request_body)
         .timeout(Duration::from_secs(100))
         .send()
         .await?;
 
-        if !search_response.status().is_success() {
-            println!("Failed to search Mouser: code {:?}", search_response.status());
-        } else {
-            break;
-        }
-        if attempts >= max_attempts {
-            break;
-        } else {
-            attempts += 1;
-            println!("Retrying Mouser search, attempt {}", attempts);
-            // slow down api call rate exponentially to prevent limiting from mouser -> 403
-            sleep(Duration::from_millis(2u64.pow(attempts))).await;
-            continue;
-        }
+    match search_response.status().as_u16() {
+        // mouser answers 403 when the call rate is too high
+        403 => return Err(SourcingError::Quota("Mouser refused the request (code 403)".to_string())),
+        _ if !search_response.status().is_success() => {
+            return Err(SourcingError::from_status(search_response.status(), "Failed to search Mouser"));
+        },
+        _ => {},
     }
 
     let bytes = search_response.bytes().await?;
+    let mut de = serde_json::Deserializer::from_slice(&bytes);
+    deserialize::<_, MouserResponse>(&mut de).map_err(|e| {
+        println!("❌ Path error: {}", e);
+        SourcingError::Parse(e.to_string())
+    })
+}
 
-    //let json: serde_json::Value = serde_json::from_slice(&bytes)?;
-
-    // Serialize the JSON with pretty formatting
-    //let pretty = serde_json::to_string_pretty(&json)?;
-
-    // Write to a file
-    //let mut file = File::create("mouser_response.json")?;
-    //file.write_all(pretty.as_bytes())?;
+/// Creates a Mouser cart with the given (quantity, Mouser part number) rows through the Cart API and returns its cart key.
+/// The Cart API needs the order API key, `MOUSER_ORDER_API_KEY`; the search key is used when it is not set.
+pub async fn create_mouser_cart(
+    sourcing: &SourcingContext,
+    rows: &[(i32, String)],
+) -> Result<String, SourcingError> {
+    let api_key = match std::env::var("MOUSER_ORDER_API_KEY") {
+        Ok(api_key) => api_key,
+        Err(_) => sourcing::required_env("MOUSER_API_KEY")?,
+    };
+    let country_code = std::env::var("MOUSER_COUNTRY_CODE").unwrap_or(sourcing.pricing.country.clone());
+    let url = format!(
+        "{}/api/v1/cart/items/insert?apiKey={}&countryCode={}",
+        DistributorKind::Mouser.api_base_url(),
+        api_key,
+        country_code
+    );
+    let request_body = CartInsertRequest {
+        cart_key: "".into(),
+        cart_items: rows
+            .iter()
+            .map(|(quantity, mouser_pn)| CartItem {
+                mouser_part_number: mouser_pn.clone(),
+                quantity: (*quantity).max(0) as u32,
+                customer_part_number: "".into(),
+            })
+            .collect(),
+    };
 
-    let response: MouserResponse;
+    let _permit = sourcing.acquire(DistributorKind::Mouser).await;
+    let cart_response = sourcing.client
+        .post(&url)
+        .header("accept", "application/json")
+        .header("Content-Type", "application/json")
+        .json(&request_body)
+        .timeout(Duration::from_secs(100))
+        .send()
+        .await?;
+    if !cart_response.status().is_success() {
+        return Err(SourcingError::from_status(cart_response.status(), "Failed to create the Mouser cart"));
+    }
 
+    let bytes = cart_response.bytes().await?;
     let mut de = serde_json::Deserializer::from_slice(&bytes);
-    match deserialize::<_, MouserResponse>(&mut de) {
-        Ok(result) => {
-            response = result;
-        },
-        Err(e) => {
-            println!("❌ Path error: {}", e);
-            // return Err(format!("Error parsing JSON: {}", e).into());
-            return Box::pin(search_mouser(query_manufacturer, query_manufacturer_pn, quantity)).await;
+    let response = deserialize::<_, CartResponse>(&mut de).map_err(|e| {
+        println!("❌ Path error: {}", e);
+        SourcingError::Parse(e.to_string())
+    })?;
+
+    if let Some(message) = response.errors
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|error| error.message)
+        .next()
+    {
+        return Err(if message.to_lowercase().contains("invalid unique identifier") {
+            SourcingError::Auth(message)
+        } else {
+            SourcingError::Http(message)
+        });
+    }
+    // a part that cannot be added does not prevent the cart from being created, it only has to be added by hand
+    for cart_item in response.cart_items.unwrap_or_default() {
+        for message in cart_item.errors.unwrap_or_default().into_iter().filter_map(|error| error.message) {
+            println!("Mouser cart: {} not added ({})", cart_item.mouser_part_number.clone().unwrap_or_default(), message);
         }
-    };
+    }
 
-    match response.search_results {
-        Some(search_results) => {
-            for part in search_results.parts {
-                let manufacturer = part.manufacturer.unwrap_or_default();
-                let manufacturer_pn = part.manufacturer_part_number.unwrap_or_default();
-                let mouser_pn = part.mouser_part_number.unwrap_or_default();
-                // assure we return only the requested item
-                if manufacturer_pn != query_manufacturer_pn && mouser_pn != query_manufacturer_pn {
-                    continue;
-                }
-                let mouser_part = MouserPart {
-                    manufacturer: manufacturer,
-                    manufacturer_pn: manufacturer_pn,
-                    description: part.description.unwrap_or_default(),
-                    mouser_pn: mouser_pn,
-                    product_url: part.product_detail_url.unwrap_or_default(),
-                    unit_price: match part.price_breaks {
-                        Some(price_breaks) => {
-                            let mut unit_price = 0.0;
-                            for price in price_breaks {
-                                if quantity >= price.Quantity {
-                                    unit_price = price.Price
-                                        .strip_suffix(" €")
-                                        .unwrap_or("0.0")
-                                        .replace(",", ".")
-                                        .parse::<f64>()
-                                        .unwrap_or(0.0);
-                                }
-                            }
-                            unit_price
-                        },
-                        None => {
-                            return Ok(None);
-                        }
-                    },
-                    availability: part.availability
-                        .clone()
-                        .unwrap_or_default()
-                        .strip_suffix(" In Stock")
-                        .unwrap_or_default()
-                        .parse::<u32>()
-                        .unwrap_or_default(),
-                };
-                return Ok(Some(mouser_part));
-            }
-            Ok(None)
-        },
-        None => Ok(None),
+    match response.cart_key {
+        Some(cart_key) if !cart_key.is_empty() => Ok(cart_key),
+        _ => Err(SourcingError::Http("Mouser did not return a cart key".to_string())),
     }
 }
+
+/// Link to a Mouser cart, on the website set by `MOUSER_CART_PAGE_URL` (default https://www.mouser.it/Cart/).
+pub fn mouser_cart_url(cart_key: &str) -> String {
+    let cart_page = std::env::var("MOUSER_CART_PAGE_URL").unwrap_or("https://www.mouser.it/Cart/".to_string());
+    format!("{}?cartKey={}", cart_page, cart_key)
+}
+
+/// Mouser formats prices for the account's locale, e.g. "0,123 €" or "$0.123": keep only the number.
+fn parse_mouser_price(price: &str) -> f64 {
+    let number: String = price
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == ',' || *c == '.')
+        .collect();
+    // prices always have decimals: the last separator is the decimal one, the others group thousands
+    let number = match number.rfind([',', '.']) {
+        Some(index) => format!("{}.{}", number[..index].replace([',', '.'], ""), &number[index + 1..]),
+        None => number,
+    };
+    number.parse::<f64>().unwrap_or(0.0)
+}
@@ -0,0 +1,42 @@
+use sqlx::PgPool;
+use crate::data::errors::DataError;
+use crate::models::stock_watch::Notification;
+
+pub async fn add_notification(pool: &PgPool, user_id: i32, message: String, link: Option<String>) -> Result<(), DataError> {
+    sqlx::query!(
+        "INSERT INTO notifications (user_id, message, link) VALUES ($1, $2, $3)",
+        user_id,
+        message,
+        link
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+pub async fn get_unread_notifications(pool: &PgPool, user_id: i32) -> Result<Vec<Notification>, DataError> {
+    let notifications = sqlx::query_as!(
+        Notification,
+        r#"SELECT id, user_id, message, link, created_at as "created_at: chrono::DateTime<chrono::Utc>"
+         FROM notifications WHERE user_id = $1 AND NOT read ORDER BY created_at DESC"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(notifications)
+}
+
+/// Dismisses a notification, only when it belongs to `user_id`.
+pub async fn mark_notification_read(pool: &PgPool, notification_id: i32, user_id: i32) -> Result<(), DataError> {
+    sqlx::query!(
+        "UPDATE notifications SET read = TRUE WHERE id = $1 AND user_id = $2",
+        notification_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
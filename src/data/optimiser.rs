@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use crate::data::distributor;
+use crate::models::distributor_models::{CostSummary, DistributorKind, DistributorQuote, DistributorTotal, ItemAssignment, Offer, ShippingRule};
+use crate::models::item::OrderItem;
+
+// a local search pass never needs more than a handful of iterations, this only guards against cycles
+const MAX_IMPROVEMENT_PASSES: usize = 50;
+
+/// Ways of buying a single item: one offer per distributor that can supply it alone,
+/// or the split across distributors when none of them can.
+pub fn candidate_plans(quotes: &[DistributorQuote], quantity: u32) -> Vec<Vec<Offer>> {
+    let plans: Vec<Vec<Offer>> = quotes
+        .iter()
+        .filter_map(|quote| quote.offer_for(quantity))
+        .map(|offer| vec![offer])
+        .collect();
+    match plans.is_empty() {
+        true => distributor::split_across_distributors(quotes, quantity).into_iter().collect(),
+        false => plans,
+    }
+}
+
+fn plan_price(plan: &[Offer]) -> f64 {
+    plan.iter().map(|offer| offer.extended_price()).sum()
+}
+
+fn plan_distributors(plan: &[Offer]) -> Vec<DistributorKind> {
+    plan.iter().map(|offer| offer.quote.distributor).collect()
+}
+
+/// Index of the cheapest plan, the first one winning ties (same rule as `select_best_offer`).
+fn cheapest_plan(plans: &[Vec<Offer>], allowed: impl Fn(&[Offer]) -> bool) -> Option<usize> {
+    plans
+        .iter()
+        .enumerate()
+        .filter(|(_, plan)| allowed(plan.as_slice()))
+        .fold(None, |best: Option<(usize, f64)>, (index, plan)| match best {
+            Some((_, best_price)) if best_price <= plan_price(plan) => best,
+            _ => Some((index, plan_price(plan))),
+        })
+        .map(|(index, _)| index)
+}
+
+fn subtotals(candidates: &[Vec<Vec<Offer>>], choice: &[Option<usize>]) -> HashMap<DistributorKind, f64> {
+    let mut subtotals: HashMap<DistributorKind, f64> = HashMap::new();
+    for (plans, chosen) in candidates.iter().zip(choice) {
+        if let Some(index) = chosen {
+            for offer in &plans[*index] {
+                *subtotals.entry(offer.quote.distributor).or_insert(0.0) += offer.extended_price();
+            }
+        }
+    }
+    subtotals
+}
+
+fn landed_cost(
+    candidates: &[Vec<Vec<Offer>>],
+    choice: &[Option<usize>],
+    rules: &HashMap<DistributorKind, ShippingRule>,
+) -> f64 {
+    subtotals(candidates, choice)
+        .into_iter()
+        .map(|(kind, subtotal)| subtotal + rules.get(&kind).map_or(0.0, |rule| rule.cost_for(subtotal) + rule.handling_for(subtotal)))
+        .sum()
+}
+
+/// Moves single items to other plans (within the allowed distributors) as long as the landed cost drops.
+fn improve(
+    candidates: &[Vec<Vec<Offer>>],
+    choice: &mut [Option<usize>],
+    rules: &HashMap<DistributorKind, ShippingRule>,
+    allowed: &impl Fn(&[Offer]) -> bool,
+) {
+    let mut current = landed_cost(candidates, choice, rules);
+    for _ in 0..MAX_IMPROVEMENT_PASSES {
+        let mut improved = false;
+        for item_index in 0..candidates.len() {
+            for plan_index in 0..candidates[item_index].len() {
+                if choice[item_index] == Some(plan_index) || !allowed(candidates[item_index][plan_index].as_slice()) {
+                    continue;
+                }
+                let previous = choice[item_index].replace(plan_index);
+                let cost = landed_cost(candidates, choice, rules);
+                if cost + 1e-9 < current {
+                    current = cost;
+                    improved = true;
+                } else {
+                    choice[item_index] = previous;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// Assigns the items of an order to distributors minimising the landed cost (item prices plus
+/// the shipping and handling of every basket). Every subset of distributors is tried, starting from the cheapest
+/// plan of each item within the subset and then moving single items while that lowers the total.
+/// Returns the chosen plan of each item (empty when the item cannot be bought) and a cost summary.
+pub fn optimise_order(
+    items: &[(OrderItem, Vec<DistributorQuote>)],
+    rules: &HashMap<DistributorKind, ShippingRule>,
+) -> (Vec<Vec<Offer>>, CostSummary) {
+    let candidates: Vec<Vec<Vec<Offer>>> = items
+        .iter()
+        .map(|(item, quotes)| candidate_plans(quotes, item.quantity as u32))
+        .collect();
+
+    let greedy: Vec<Option<usize>> = candidates
+        .iter()
+        .map(|plans| cheapest_plan(plans, |_| true))
+        .collect();
+    let greedy_total_cost = landed_cost(&candidates, &greedy, rules);
+
+    let mut kinds: Vec<DistributorKind> = Vec::new();
+    for offer in candidates.iter().flatten().flatten() {
+        if !kinds.contains(&offer.quote.distributor) {
+            kinds.push(offer.quote.distributor);
+        }
+    }
+
+    let mut best = greedy.clone();
+    let mut best_cost = greedy_total_cost;
+    for mask in 1..(1u32 << kinds.len()) {
+        let subset: Vec<DistributorKind> = kinds
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| mask & (1 << index) != 0)
+            .map(|(_, kind)| *kind)
+            .collect();
+        let allowed = |plan: &[Offer]| plan.iter().all(|offer| subset.contains(&offer.quote.distributor));
+
+        let mut choice: Vec<Option<usize>> = Vec::with_capacity(candidates.len());
+        let mut feasible = true;
+        for plans in &candidates {
+            match cheapest_plan(plans, allowed) {
+                Some(index) => choice.push(Some(index)),
+                None if plans.is_empty() => choice.push(None),
+                None => {
+                    feasible = false;
+                    break;
+                }
+            }
+        }
+        if !feasible {
+            continue;
+        }
+
+        improve(&candidates, &mut choice, rules, &allowed);
+        let cost = landed_cost(&candidates, &choice, rules);
+        if cost + 1e-9 < best_cost {
+            best_cost = cost;
+            best = choice;
+        }
+    }
+
+    let mut distributors: Vec<DistributorTotal> = subtotals(&candidates, &best)
+        .into_iter()
+        .map(|(kind, subtotal)| DistributorTotal {
+            distributor: kind,
+            subtotal,
+            shipping: rules.get(&kind).map_or(0.0, |rule| rule.cost_for(subtotal)),
+            handling: rules.get(&kind).map_or(0.0, |rule| rule.handling_for(subtotal)),
+        })
+        .collect();
+    distributors.sort_by_key(|total| kinds.iter().position(|kind| *kind == total.distributor));
+
+    let plans: Vec<Vec<Offer>> = candidates
+        .iter()
+        .zip(&best)
+        .map(|(plans, chosen)| chosen.map(|index| plans[index].clone()).unwrap_or_default())
+        .collect();
+
+    let assignments = items
+        .iter()
+        .zip(&plans)
+        .zip(candidates.iter().zip(&greedy))
+        .map(|(((item, _), plan), (item_candidates, greedy_choice))| ItemAssignment {
+            manufacturer: item.manufacturer.clone(),
+            manufacturer_pn: item.manufacturer_pn.clone(),
+            distributors: plan_distributors(plan),
+            greedy_distributors: greedy_choice
+                .map(|index| plan_distributors(&item_candidates[index]))
+                .unwrap_or_default(),
+            cost: plan_price(plan),
+        })
+        .collect();
+
+    let summary = CostSummary {
+        distributors,
+        items: assignments,
+        total_cost: best_cost,
+        greedy_total_cost,
+        saving: greedy_total_cost - best_cost,
+        custom_vendors: Vec::new(),
+    };
+    (plans, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::distributor_models::QuotePriceBreak;
+
+    fn item(manufacturer_pn: &str, quantity: i32) -> OrderItem {
+        OrderItem {
+            order_id: 1,
+            manufacturer: "Texas Instruments".to_string(),
+            manufacturer_pn: manufacturer_pn.to_string(),
+            quantity,
+            proposal: "Elettronica generale".to_string(),
+            project: "Varie per lab".to_string(),
+            mouser_pn: None,
+            digikey_pn: None,
+            farnell_pn: None,
+            lcsc_pn: None,
+            tme_pn: None,
+            purchase_quantity: None,
+            sourcing_notes: None,
+            preferred_distributor: None,
+            pinned_distributor_pn: None,
+            reference_designators: None,
+            received_quantity: 0,
+            sourcing_outdated: false,
+        }
+    }
+
+    fn quote(distributor: DistributorKind, manufacturer_pn: &str, unit_price: f64, availability: u32) -> DistributorQuote {
+        DistributorQuote {
+            distributor,
+            manufacturer: "Texas Instruments".to_string(),
+            manufacturer_pn: manufacturer_pn.to_string(),
+            description: "".to_string(),
+            distributor_pn: format!("{}-{}", distributor.name(), manufacturer_pn),
+            product_url: "".to_string(),
+            unit_price,
+            availability,
+            minimum_order_quantity: 1,
+            price_breaks: vec![QuotePriceBreak { quantity: 1, unit_price }],
+        }
+    }
+
+    fn rules() -> HashMap<DistributorKind, ShippingRule> {
+        HashMap::from([
+            (DistributorKind::Mouser, ShippingRule { cost: 20.0, free_threshold: Some(50.0), handling: 0.0 }),
+            (DistributorKind::DigiKey, ShippingRule { cost: 18.0, free_threshold: Some(50.0), handling: 0.0 }),
+        ])
+    }
+
+    #[test]
+    fn item_moves_to_the_basket_with_free_shipping() {
+        // per item Digi-Key is cheaper for the second part, but its basket would pay 18 of shipping
+        let items = vec![
+            (item("LM358", 1), vec![quote(DistributorKind::Mouser, "LM358", 60.0, 100)]),
+            (item("NE555", 1), vec![
+                quote(DistributorKind::Mouser, "NE555", 12.0, 100),
+                quote(DistributorKind::DigiKey, "NE555", 10.0, 100),
+            ]),
+        ];
+        let (plans, summary) = optimise_order(&items, &rules());
+
+        assert_eq!(plan_distributors(&plans[1]), vec![DistributorKind::Mouser]);
+        assert_eq!(summary.items[1].greedy_distributors, vec![DistributorKind::DigiKey]);
+        assert!((summary.greedy_total_cost - 88.0).abs() < 1e-9);
+        assert!((summary.total_cost - 72.0).abs() < 1e-9);
+        assert!((summary.saving - 16.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn greedy_assignment_is_kept_when_nothing_is_cheaper() {
+        let items = vec![
+            (item("LM358", 1), vec![
+                quote(DistributorKind::Mouser, "LM358", 60.0, 100),
+                quote(DistributorKind::DigiKey, "LM358", 70.0, 100),
+            ]),
+            (item("NE555", 1), vec![
+                quote(DistributorKind::Mouser, "NE555", 12.0, 100),
+                quote(DistributorKind::DigiKey, "NE555", 10.0, 100),
+            ]),
+            // not sold anywhere: no plan, the rest of the order is still assigned
+            (item("XYZ123", 1), Vec::new()),
+        ];
+        let (plans, summary) = optimise_order(&items, &HashMap::new());
+
+        assert_eq!(plan_distributors(&plans[0]), vec![DistributorKind::Mouser]);
+        assert_eq!(plan_distributors(&plans[1]), vec![DistributorKind::DigiKey]);
+        assert!(plans[2].is_empty());
+        assert!((summary.total_cost - 70.0).abs() < 1e-9);
+        assert!(summary.saving.abs() < 1e-9);
+    }
+}
@@ -1,22 +1,67 @@
-use crate::data::{item, errors::DataError};
-use crate::models::digikey_api_models::DigiKeyPart;
-use crate::models::item::OrderItem;
-use crate::models::mouser_api_models::MouserPart;
-use futures::stream::{FuturesUnordered, StreamExt};
-use sqlx::{PgPool, types::time::Date};
+use std::collections::HashMap;
+use crate::data::{item, errors::{DataError, SourcingError}, sourcing::SourcingContext};
+use crate::models::distributor_models::{CostSummary, CustomVendorTotal, DistributorKind, DistributorQuote, Offer, ShippingRule, SourcingIssue};
+use crate::models::item::{CustomOrderItem, OrderItem};
+use crate::models::order_status::{OrderStatus, OrderTransition};
+use crate::models::pricing::PricingSettings;
+use futures::stream::{self, StreamExt};
+use sqlx::{PgExecutor, PgPool, types::time::Date};
 use time::format_description;
 use umya_spreadsheet::{Spreadsheet};
-use crate::data::{mouser_apis};
 
 use crate::data::excel;
 
-use super::digikey_apis;
+use super::{digikey_apis, distributor, mouser_apis, optimiser, receiving};
 
 #[derive(Debug, Clone)]
 struct ItemProcessingResult {
     item: OrderItem,
-    mouser_part: Option<MouserPart>,
-    digikey_part: Option<DigiKeyPart>
+    quotes: Vec<DistributorQuote>,
+    errors: Vec<(DistributorKind, SourcingError)>,
+}
+
+/// An item as submitted from the edit order page.
+#[derive(Debug, Clone)]
+pub struct SubmittedItem {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub quantity: i32,
+    pub proposal: String,
+    pub project: String,
+    pub preferred_distributor: Option<DistributorKind>,
+    pub pinned_distributor_pn: Option<String>,
+}
+
+/// An item added to an order, with the distributor part numbers it is already known by.
+#[derive(Debug, Clone)]
+pub struct NewOrderItem {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub quantity: i32,
+    pub proposal: String,
+    pub project: String,
+    pub mouser_pn: Option<String>,
+    pub digikey_pn: Option<String>,
+    pub farnell_pn: Option<String>,
+    pub lcsc_pn: Option<String>,
+    pub tme_pn: Option<String>,
+}
+
+impl NewOrderItem {
+    pub fn new(manufacturer: String, manufacturer_pn: String, quantity: i32, proposal: String, project: String) -> NewOrderItem {
+        NewOrderItem {
+            manufacturer,
+            manufacturer_pn,
+            quantity,
+            proposal,
+            project,
+            mouser_pn: None,
+            digikey_pn: None,
+            farnell_pn: None,
+            lcsc_pn: None,
+            tme_pn: None,
+        }
+    }
 }
 
 #[derive(sqlx::FromRow, Debug, Clone)]
@@ -24,11 +69,12 @@ pub struct Order {
     pub id: i32,
     pub author_id: i32,
     pub date: Date,
-    pub ready: bool,
-    pub confirmed: bool,
     pub description: String,
     pub area_division: String,
-    pub area_sub_area: String
+    pub area_sub_area: String,
+    pub mouser_cart_url: Option<String>,
+    pub digikey_list_url: Option<String>,
+    pub status: String,
 }
 
 impl Order {
@@ -36,23 +82,21 @@ impl Order {
         let format = format_description::parse("[day]/[month]/[year]").unwrap();
         self.date.format(&format).unwrap_or("".to_string())
     }
+    pub fn status(&self) -> OrderStatus {
+        OrderStatus::from_name(&self.status).unwrap_or(OrderStatus::Draft)
+    }
     pub fn get_status(&self) -> &str {
-        if self.confirmed {
-            "All done! ✅"
-        } else if self.ready {
-            "Waiting for approval ..."
-        } else {
-            "To be completed ..."
-        }
+        self.status().label()
     }
     pub fn get_bg_color(&self) -> &str {
-        if self.confirmed {
-          " #ACF39D"
-        } else if self.ready {
-            " #FFC107"
-        } else {
-            " #E85F5C"
-        }
+        self.status().color()
+    }
+    /// Transitions a user with `role` can apply to the order now, shown as buttons on the homepages.
+    pub fn transitions(&self, role: &str, is_author: bool) -> Vec<OrderTransition> {
+        OrderTransition::all()
+            .into_iter()
+            .filter(|transition| transition.is_allowed(role, is_author, self.status()))
+            .collect()
     }
 }
 
@@ -68,10 +112,12 @@ pub async fn get_order_from_author_id(author_id: i32, pool: &PgPool) -> Result<V
     Ok(user_orders)
 }
 
-pub async fn get_ready_orders(pool: &PgPool) -> Result<Vec<Order>, DataError> {
+/// Orders submitted to the board, in any later status.
+pub async fn get_submitted_orders(pool: &PgPool) -> Result<Vec<Order>, DataError> {
     let user_orders = sqlx::query_as!(
         Order,
-        "SELECT * FROM orders WHERE ready = true ORDER BY date DESC, id DESC"
+        "SELECT * FROM orders WHERE status <> $1 ORDER BY date DESC, id DESC",
+        OrderStatus::Draft.name()
     )
     .fetch_all(pool)
     .await
@@ -79,10 +125,15 @@ pub async fn get_ready_orders(pool: &PgPool) -> Result<Vec<Order>, DataError> {
     Ok(user_orders)
 }
 
-pub async fn get_confirmed_orders(pool: &PgPool) -> Result<Vec<Order>, DataError> {
+pub async fn get_approved_orders(pool: &PgPool) -> Result<Vec<Order>, DataError> {
+    let statuses: Vec<String> = OrderStatus::approved_statuses()
+        .iter()
+        .map(|status| status.name().to_string())
+        .collect();
     let user_orders = sqlx::query_as!(
         Order,
-        "SELECT * FROM orders WHERE confirmed = true ORDER BY date DESC, id DESC"
+        "SELECT * FROM orders WHERE status = ANY($1) ORDER BY date DESC, id DESC",
+        &statuses
     )
     .fetch_all(pool)
     .await
@@ -102,48 +153,100 @@ pub async fn get_order_from_id(order_id: i32, pool: &PgPool) -> Result<Order, Da
     Ok(user_orders)
 }
 
-pub async fn mark_order_ready(pool: &PgPool, order_id: i32) -> Result<(), DataError> {
-    sqlx::query!(
-        "UPDATE orders SET ready = true WHERE id = $1",
-        order_id
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| DataError::Query(e))?;
-    Ok(())
-}
-
-pub async fn mark_order_unready(pool: &PgPool, order_id: i32) -> Result<(), DataError> {
-    sqlx::query!(
-        "UPDATE orders SET ready = false WHERE id = $1",
-        order_id
+/// Applies the transition to the order, unless its status changed in the meantime and the transition no longer applies.
+pub async fn apply_transition<'e>(executor: impl PgExecutor<'e>, order_id: i32, transition: OrderTransition) -> Result<(), DataError> {
+    let from_statuses: Vec<String> = transition
+        .from_statuses()
+        .iter()
+        .map(|status| status.name().to_string())
+        .collect();
+    let result = sqlx::query!(
+        "UPDATE orders SET status = $1 WHERE id = $2 AND status = ANY($3)",
+        transition.target().name(),
+        order_id,
+        &from_statuses
     )
-    .execute(pool)
+    .execute(executor)
     .await
     .map_err(|e| DataError::Query(e))?;
+    if result.rows_affected() == 0 {
+        return Err(DataError::Internal(format!(
+            "Order {} cannot be moved to {} from its current status",
+            order_id,
+            transition.target().name()
+        )));
+    }
     Ok(())
 }
 
-pub async fn mark_order_confirmed(pool: &PgPool, order_id: i32) -> Result<(), DataError> {
+/// Creates a Mouser cart with the Mouser allocations of the order and stores its link,
+/// returns `None` when nothing has to be bought from Mouser. The cart of the current sourcing is reused if it exists.
+pub async fn create_mouser_cart(pool: &PgPool, sourcing: &SourcingContext, order_id: i32) -> Result<Option<String>, DataError> {
+    if let Some(cart_url) = get_order_from_id(order_id, pool).await?.mouser_cart_url {
+        return Ok(Some(cart_url));
+    }
+    let allocations = item::get_allocations_for_distributor(order_id, DistributorKind::Mouser, pool).await?;
+    let rows: Vec<(i32, String)> = allocations
+        .into_iter()
+        .map(|allocation| (allocation.quantity, allocation.distributor_pn))
+        .collect();
+    let cart_url = match rows.is_empty() {
+        true => None,
+        false => {
+            let cart_key = mouser_apis::create_mouser_cart(sourcing, &rows)
+                .await
+                .map_err(|e| DataError::Internal(format!("Failed to create the Mouser cart: {}", e)))?;
+            Some(mouser_apis::mouser_cart_url(&cart_key))
+        },
+    };
     sqlx::query!(
-        "UPDATE orders SET confirmed = true WHERE id = $1",
+        "UPDATE orders SET mouser_cart_url = $1 WHERE id = $2",
+        cart_url,
         order_id
     )
     .execute(pool)
     .await
-    .map_err(|e| DataError::Query(e))?;
-    Ok(())
+    .map_err(DataError::Query)?;
+    Ok(cart_url)
 }
 
-pub async fn mark_order_unconfirmed(pool: &PgPool, order_id: i32) -> Result<(), DataError> {
+/// Creates a Digi-Key list named after the order with its Digi-Key allocations and stores its link,
+/// returns `None` when nothing has to be bought from Digi-Key. The list of the current sourcing is reused if it exists.
+pub async fn create_digikey_list(pool: &PgPool, sourcing: &SourcingContext, order_id: i32) -> Result<Option<String>, DataError> {
+    let order = get_order_from_id(order_id, pool).await?;
+    if let Some(list_url) = order.digikey_list_url {
+        return Ok(Some(list_url));
+    }
+    let allocations = item::get_allocations_for_distributor(order_id, DistributorKind::DigiKey, pool).await?;
+    let rows: Vec<digikey_apis::ListRow> = allocations
+        .into_iter()
+        .map(|allocation| digikey_apis::ListRow {
+            quantity: allocation.quantity,
+            digikey_pn: allocation.distributor_pn,
+            manufacturer: allocation.manufacturer,
+            manufacturer_pn: allocation.manufacturer_pn,
+        })
+        .collect();
+    let list_url = match rows.is_empty() {
+        true => None,
+        false => {
+            // list names are limited in length, the order id keeps them unique
+            let list_name: String = format!("PoliTOcean #{} {}", order_id, order.description).chars().take(50).collect();
+            let list_id = digikey_apis::create_digikey_list(sourcing, list_name.trim(), &rows)
+                .await
+                .map_err(|e| DataError::Internal(format!("Failed to create the Digi-Key list: {}", e)))?;
+            Some(digikey_apis::digikey_list_url(&list_id))
+        },
+    };
     sqlx::query!(
-        "UPDATE orders SET confirmed = false WHERE id = $1",
+        "UPDATE orders SET digikey_list_url = $1 WHERE id = $2",
+        list_url,
         order_id
     )
     .execute(pool)
     .await
-    .map_err(|e| DataError::Query(e))?;
-    Ok(())
+    .map_err(DataError::Query)?;
+    Ok(list_url)
 }
 
 pub async fn create_order(
@@ -178,21 +281,19 @@ pub async fn create_order(
     Ok(order_id)
 }
 
-pub async fn create_order_with_id(
+pub async fn update_order_details(
     pool: &PgPool,
     order_id: i32,
-    author_id: i32,
     description: String,
     area_division: String,
     area_sub_area: String,
 ) -> Result<(), DataError> {
     sqlx::query!(
-        "INSERT INTO orders (id, author_id, description, area_division, area_sub_area) VALUES ($1, $2, $3, $4, $5)",
-        order_id,
-        author_id,
+        "UPDATE orders SET description = $1, area_division = $2, area_sub_area = $3 WHERE id = $4",
         description,
         area_division,
-        area_sub_area
+        area_sub_area,
+        order_id
     )
     .execute(pool)
     .await
@@ -211,30 +312,23 @@ pub async fn delete_order(pool: &PgPool, order_id: i32) -> Result<(), DataError>
     Ok(())
 }
 
-pub async fn add_item_to_order(
-    pool: &PgPool,
-    order_id: i32,
-    manufacturer: String,
-    manufacturer_pn: String,
-    quantity: i32,
-    proposal: String,
-    project: String,
-    mouser_pn: Option<String>,
-    digikey_pn: Option<String>,
-) -> Result<(), DataError> {
+pub async fn add_item_to_order(pool: &PgPool, order_id: i32, item: &NewOrderItem) -> Result<(), DataError> {
     sqlx::query!(
-        "INSERT INTO order_items (order_id, manufacturer, manufacturer_pn, quantity, proposal, project, mouser_pn, digikey_pn) 
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "INSERT INTO order_items (order_id, manufacturer, manufacturer_pn, quantity, proposal, project, mouser_pn, digikey_pn, farnell_pn, lcsc_pn, tme_pn) 
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
         ON CONFLICT (order_id, manufacturer, manufacturer_pn)
-        DO UPDATE SET quantity = order_items.quantity + EXCLUDED.quantity",
+        DO UPDATE SET quantity = order_items.quantity + EXCLUDED.quantity, sourcing_outdated = TRUE",
         order_id,
-        manufacturer,
-        manufacturer_pn,
-        quantity,
-        proposal,
-        project,
-        mouser_pn,
-        digikey_pn
+        item.manufacturer,
+        item.manufacturer_pn,
+        item.quantity,
+        item.proposal,
+        item.project,
+        item.mouser_pn,
+        item.digikey_pn,
+        item.farnell_pn,
+        item.lcsc_pn,
+        item.tme_pn
     )
     .execute(pool)
     .await
@@ -242,222 +336,475 @@ pub async fn add_item_to_order(
     Ok(())
 }
 
-pub async fn add_to_bom_and_db(
+/// Makes the items of the order match the submitted ones: missing items are removed, the others
+/// are updated in place, keeping their sourcing results, allocations and watches.
+pub async fn update_order_items(pool: &PgPool, order_id: i32, items: &[SubmittedItem]) -> Result<(), DataError> {
+    let manufacturers: Vec<String> = items.iter().map(|i| i.manufacturer.clone()).collect();
+    let manufacturer_pns: Vec<String> = items.iter().map(|i| i.manufacturer_pn.clone()).collect();
+    sqlx::query!(
+        "DELETE FROM order_items WHERE order_id = $1 AND NOT EXISTS (
+            SELECT 1 FROM UNNEST($2::TEXT[], $3::TEXT[]) AS submitted(manufacturer, manufacturer_pn)
+            WHERE submitted.manufacturer = order_items.manufacturer AND submitted.manufacturer_pn = order_items.manufacturer_pn
+        )",
+        order_id,
+        &manufacturers,
+        &manufacturer_pns
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+
+    for submitted in items {
+        sqlx::query!(
+            "INSERT INTO order_items (order_id, manufacturer, manufacturer_pn, quantity, proposal, project)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (order_id, manufacturer, manufacturer_pn)
+            DO UPDATE SET quantity = EXCLUDED.quantity, proposal = EXCLUDED.proposal, project = EXCLUDED.project,
+                sourcing_outdated = order_items.sourcing_outdated OR order_items.quantity <> EXCLUDED.quantity",
+            order_id,
+            submitted.manufacturer,
+            submitted.manufacturer_pn,
+            submitted.quantity,
+            submitted.proposal,
+            submitted.project
+        )
+        .execute(pool)
+        .await
+        .map_err(DataError::Query)?;
+        item::set_item_distributor_preference(
+            pool,
+            order_id,
+            submitted.manufacturer.clone(),
+            submitted.manufacturer_pn.clone(),
+            submitted.preferred_distributor,
+            submitted.pinned_distributor_pn.clone(),
+        ).await?;
+    }
+    Ok(())
+}
+
+/// Stores the chosen offers of an item: distributor part numbers, allocations and the item notes.
+pub async fn store_item_offers(
     pool: &PgPool,
     order_id: i32,
-    manufacturer: String,
-    manufacturer_pn: String,
-    quantity: i32,
-    description: String,
-    unit_price: f64,
-    proposal: String,
-    product_url: String,
-    project: String,
-    mouser_pn: Option<String>,
-    digikey_pn: Option<String>,
-    book: &mut Spreadsheet,
+    item: &OrderItem,
+    offers: &[Offer],
+    notes: Option<String>,
 ) -> Result<(), DataError>{
     item::set_item_pn(
         pool,
         order_id,
-        manufacturer.clone(),
-        manufacturer_pn.clone(), 
-        mouser_pn,
-        digikey_pn,
+        item.manufacturer.clone(),
+        item.manufacturer_pn.clone(), 
+        offers,
     ).await?;
-    excel::add_item_to_bom(
-        book,
-        manufacturer,
-        manufacturer_pn,
-        quantity,
-        description,
-        unit_price,
-        proposal,
-        product_url,
-        project,
-        "".to_string()).map_err(|e| DataError::FailedQuery(e.to_string()))?;
+    for offer in offers {
+        item::add_item_allocation(pool, order_id, item.manufacturer.clone(), item.manufacturer_pn.clone(), offer).await?;
+    }
+    item::set_item_sourcing_notes(pool, order_id, item.manufacturer.clone(), item.manufacturer_pn.clone(), notes).await?;
     Ok(())
 }
 
-pub async fn generate_bom(pool: &PgPool, order_id: i32) -> Result<(), DataError> {
-    println!("Generating BOM for order {}", order_id);
-    // get order info
-    let order: Order = get_order_from_id(order_id, pool).await?;
-    let order_items = item::get_items_from_order(order_id, pool).await?;
+/// Adds a row to each distributor BOM the item is bought from.
+/// An item with no offers is reported with zero quantity in the Mouser BOM.
+/// `item_notes` is appended to the notes of every row (e.g. why the item was moved to another distributor).
+pub fn add_item_to_books(
+    books: &mut HashMap<DistributorKind, Spreadsheet>,
+    item: &OrderItem,
+    offers: &[Offer],
+    item_notes: Option<String>,
+    delivered: String,
+    pricing: &PricingSettings,
+) -> Result<(), DataError>{
+    if offers.is_empty() {
+        let book = books.get_mut(&DistributorKind::Mouser)
+            .ok_or_else(|| DataError::Internal("Missing Mouser BOM".to_string()))?;
+        let row = excel::BomRow {
+            manifacturer: item.manufacturer.clone(),
+            manifacturer_pn: item.manufacturer_pn.clone(),
+            quantity: 0,
+            description: "".to_string(),
+            unit_price: 0.0,
+            proposal: item.proposal.clone(),
+            link: "".to_string(),
+            project: item.project.clone(),
+            delivered,
+            requested_quantity: item.quantity,
+            notes: item_notes.unwrap_or_default(),
+        };
+        excel::add_item_to_bom(book, row, pricing).map_err(DataError::FailedQuery)?;
+        return Ok(());
+    }
 
-    // create excel files
-    let mut mouser_book = excel::create_bom_file();
-    let mut digikey_book = excel::create_bom_file();
-
-    let mut tasks = FuturesUnordered::new();
-
-    for item in order_items {
-        let item = item.clone();
-        tasks.push(async move {
-            let (mouser_part_res, digikey_part_res) = tokio::join!(
-                mouser_apis::search_mouser(
-                &item.manufacturer,
-                &item.manufacturer_pn,
-                item.quantity as u32),
-                digikey_apis::digikey_search(&item.manufacturer, 
-                &item.manufacturer_pn, 
-                item.quantity as u32)
-            );
-            let mouser_part_opt = mouser_part_res
-                .unwrap_or(None);
-            let digikey_part_opt = digikey_part_res
-                .unwrap_or(None);
-            ItemProcessingResult { item: item, mouser_part: mouser_part_opt, digikey_part: digikey_part_opt }
-        });
+    for offer in offers {
+        let notes = match offers.len() {
+            1 => offer.adjustment_note(item.quantity as u32),
+            _ => format!(
+                "Split across distributors: {} of {} bought here, the rest from {}",
+                offer.quantity,
+                item.quantity,
+                offers
+                    .iter()
+                    .filter(|other| other.quote.distributor != offer.quote.distributor)
+                    .map(|other| format!("{} ({})", other.quote.distributor.name(), other.quantity))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+        let notes = match (&item_notes, notes.is_empty()) {
+            (Some(item_notes), true) => item_notes.clone(),
+            (Some(item_notes), false) => format!("{}; {}", notes, item_notes),
+            (None, _) => notes,
+        };
+        let book = books.get_mut(&offer.quote.distributor)
+            .ok_or_else(|| DataError::Internal(format!("Missing BOM for {}", offer.quote.distributor.name())))?;
+        let row = excel::BomRow {
+            manifacturer: offer.quote.manufacturer.clone(),
+            manifacturer_pn: offer.quote.manufacturer_pn.clone(),
+            quantity: offer.quantity as i32,
+            description: offer.quote.description.clone(),
+            unit_price: offer.unit_price,
+            proposal: item.proposal.clone(),
+            link: offer.quote.product_url.clone(),
+            project: item.project.clone(),
+            delivered: delivered.clone(),
+            requested_quantity: item.quantity,
+            notes,
+        };
+        excel::add_item_to_bom(book, row, pricing).map_err(DataError::FailedQuery)?;
     }
+    Ok(())
+}
 
-    let mut results: Vec<ItemProcessingResult> = Vec::new();
-    while let Some(result) = tasks.next().await {
-        results.push(result);
+/// Builds the per-distributor BOMs from the items, allocations and sourcing results stored in the database.
+/// Returns `None` when the order has items that have never been sourced.
+pub async fn build_bom_books(pool: &PgPool, pricing: &PricingSettings, order_id: i32) -> Result<Option<HashMap<DistributorKind, Spreadsheet>>, DataError> {
+    let results = item::get_sourcing_results(order_id, pool).await?;
+    let order_items = item::get_items_from_order(order_id, pool).await?;
+    if results.is_empty() && !order_items.is_empty() {
+        return Ok(None);
     }
+    let allocations = item::get_allocations_from_order(order_id, pool).await?;
+    let receipts = receiving::get_receipts_for_order(pool, order_id).await?;
 
-    for result in results {
-        match (result.mouser_part, result.digikey_part) {
-            (Some(mouser_part), Some(digikey_part)) => {
-                println!("man: {} - id: {} - mouser_price: {} - digikey_price: {}", 
-                    result.item.manufacturer,
-                    result.item.manufacturer_pn, 
-                    mouser_part.unit_price, 
-                    digikey_part.unit_price);
-                if (mouser_part.availability >= result.item.quantity as u32)
-                && mouser_part.unit_price > 0.0
-                && (mouser_part.unit_price < digikey_part.unit_price || digikey_part.unit_price == 0.0)  {
-                    // adding to mouser book, set mouser_pn in db
-                    add_to_bom_and_db(
-                        pool,
-                        order_id,
-                        mouser_part.manufacturer.clone(),
-                        mouser_part.manufacturer_pn.clone(), 
-                        result.item.quantity,
-                        mouser_part.description,
-                        mouser_part.unit_price,
-                        result.item.proposal,
-                        mouser_part.product_url,
-                        result.item.project,
-                        Some(mouser_part.mouser_pn.clone()), 
-                        None, 
-                        &mut mouser_book
-                    ).await?;
-                } else if digikey_part.availability >= result.item.quantity as u32 
-                && digikey_part.unit_price > 0.0 {
-                    // adding to digikey book, set digikey_pn in db
-                    add_to_bom_and_db(
-                        pool,
-                        order_id,
-                        digikey_part.manufacturer.clone(),
-                        digikey_part.manufacturer_pn.clone(), 
-                        result.item.quantity,
-                        digikey_part.description,
-                        digikey_part.unit_price,
-                        result.item.proposal,
-                        digikey_part.product_url,
-                        result.item.project,
-                        None, 
-                        Some(digikey_part.digikey_pn.clone()), 
-                        &mut digikey_book
-                    ).await?;
-                } else {
-                    println!("Item not available on mouser nor digikey");
-                    add_to_bom_and_db(
-                        pool,
-                        order_id,
-                        result.item.manufacturer.clone(),
-                        result.item.manufacturer_pn.clone(), 
-                        0,
-                        "".to_string(),
-                        0.0,
-                        result.item.proposal,
-                        "".to_string(),
-                        result.item.project,
-                        None, 
-                        None, 
-                        &mut mouser_book
-                    ).await?;
-                }
-            },
-            (None, Some(digikey_part)) => { // only available on digikey
-                println!("manufacturer: {} - pn: {} - digikey_price: {}", result.item.manufacturer, result.item.manufacturer_pn, digikey_part.unit_price);
-                add_to_bom_and_db(
-                    pool,
-                    order_id,
-                    digikey_part.manufacturer.clone(),
-                    digikey_part.manufacturer_pn.clone(), 
-                    result.item.quantity,
-                    digikey_part.description,
-                    digikey_part.unit_price,
-                    result.item.proposal,
-                    digikey_part.product_url,
-                    result.item.project,
-                    None, 
-                    Some(digikey_part.digikey_pn.clone()), 
-                    &mut digikey_book
-                ).await?;
-            }
-            (Some(mouser_part), None) => { // only available on mouser
-                println!("man: {} - id: {} - mouser_price: {}", result.item.manufacturer, result.item.manufacturer_pn, mouser_part.unit_price);
-                add_to_bom_and_db(
-                        pool,
-                        order_id,
-                        mouser_part.manufacturer.clone(),
-                        mouser_part.manufacturer_pn.clone(), 
-                        result.item.quantity,
-                        mouser_part.description,
-                        mouser_part.unit_price,
-                        result.item.proposal,
-                        mouser_part.product_url,
-                        result.item.project,
-                        Some(mouser_part.mouser_pn.clone()), 
-                        None, 
-                        &mut mouser_book
-                    ).await?;
-            }
-            (None, None) => { // part not found
-                println!("man: {} - id: {} - not found", result.item.manufacturer, result.item.manufacturer_pn);
-                add_to_bom_and_db (
-                        pool,
-                        order_id,
-                        result.item.manufacturer.clone(),
-                        result.item.manufacturer_pn.clone(), 
-                        0,
-                        "".to_string(),
-                        0.0,
-                        result.item.proposal,
-                        "".to_string(),
-                        result.item.project,
-                        None, 
-                        None, 
-                        &mut mouser_book
-                    ).await?;
-            }
-        }
+    let mut books: HashMap<DistributorKind, Spreadsheet> = distributor::default_distributors()
+        .iter()
+        .map(|d| (d.kind(), excel::create_bom_file(pricing)))
+        .collect();
+
+    for item in &order_items {
+        let offers: Vec<Offer> = allocations
+            .iter()
+            .filter(|a| a.manufacturer == item.manufacturer && a.manufacturer_pn == item.manufacturer_pn)
+            .filter_map(|allocation| {
+                let quote = results
+                    .iter()
+                    .find(|r| r.manufacturer == allocation.manufacturer
+                        && r.manufacturer_pn == allocation.manufacturer_pn
+                        && r.distributor == allocation.distributor)
+                    .and_then(|r| r.to_quote())?;
+                Some(Offer {
+                    quote,
+                    quantity: allocation.quantity.max(0) as u32,
+                    unit_price: allocation.unit_price,
+                })
+            })
+            .collect();
+        let delivered = receiving::delivered_text(item, &receipts);
+        add_item_to_books(&mut books, item, &offers, item.sourcing_notes.clone(), delivered, pricing)?;
+    }
+
+    // shipping and handling as assigned by the optimisation
+    let cost_summary = get_cost_summary(pool, order_id).await?;
+    for (kind, book) in books.iter_mut() {
+        let total = cost_summary
+            .as_ref()
+            .and_then(|summary| summary.distributors.iter().find(|total| total.distributor == *kind));
+        let (shipping, handling) = match total {
+            Some(total) if total.subtotal > 0.0 => (total.shipping, total.handling),
+            _ => (0.0, 0.0),
+        };
+        excel::add_charges_to_bom(book, shipping, handling, pricing).map_err(DataError::FailedQuery)?;
     }
-    let mouser_bom_bytes = excel::save_to_bytes(&mouser_book).map_err(|e| DataError::FailedQuery(e.to_string()))?;
-    let digikey_bom_bytes = excel::save_to_bytes(&digikey_book).map_err(|e| DataError::FailedQuery(e.to_string()))?;
+    Ok(Some(books))
+}
+
+/// Regenerates the BOM files of the order from the database. Orders sourced before sourcing results
+/// were stored keep their existing files.
+pub async fn save_bom_files(pool: &PgPool, pricing: &PricingSettings, order_id: i32) -> Result<(), DataError> {
+    let order: Order = get_order_from_id(order_id, pool).await?;
+    save_custom_bom_file(pool, pricing, &order).await?;
+    let books = match build_bom_books(pool, pricing, order_id).await? {
+        Some(books) => books,
+        // nothing to rebuild the workbooks from, keep the stored ones
+        None => return Ok(()),
+    };
+
+    let book_bytes = |kind: DistributorKind| match books.get(&kind) {
+        Some(book) => excel::save_to_bytes(book).map_err(DataError::FailedQuery),
+        None => Err(DataError::Internal(format!("Missing BOM for {}", kind.name()))),
+    };
+    let mouser_bom_bytes = book_bytes(DistributorKind::Mouser)?;
+    let digikey_bom_bytes = book_bytes(DistributorKind::DigiKey)?;
+    let farnell_bom_bytes = book_bytes(DistributorKind::Farnell)?;
+    let lcsc_bom_bytes = book_bytes(DistributorKind::Lcsc)?;
+    let tme_bom_bytes = book_bytes(DistributorKind::Tme)?;
     // save bom file to db
     sqlx::query!(
-        r#"INSERT INTO order_bom (order_id, bom_file_mouser, bom_file_digikey, filename)
-            VALUES ($1, $2, $3, $4)
+        r#"INSERT INTO order_bom (order_id, bom_file_mouser, bom_file_digikey, bom_file_farnell, bom_file_lcsc, bom_file_tme, filename)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             ON CONFLICT (order_id) DO UPDATE 
             SET bom_file_mouser = EXCLUDED.bom_file_mouser,
             bom_file_digikey = EXCLUDED.bom_file_digikey,
+            bom_file_farnell = EXCLUDED.bom_file_farnell,
+            bom_file_lcsc = EXCLUDED.bom_file_lcsc,
+            bom_file_tme = EXCLUDED.bom_file_tme,
             filename = EXCLUDED.filename"#r,
         order_id,
         mouser_bom_bytes,
         digikey_bom_bytes,
+        farnell_bom_bytes,
+        lcsc_bom_bytes,
+        tme_bom_bytes,
         order.description.replace(" ", "_").to_lowercase()
     )
     .execute(pool)
     .await
-    .map_err(|e| DataError::Query(e))?;
+    .map_err(DataError::Query)?;
+
+    Ok(())
+}
 
+/// Stores the BOM of the custom items, or clears it when the order has none left.
+async fn save_custom_bom_file(pool: &PgPool, pricing: &PricingSettings, order: &Order) -> Result<(), DataError> {
+    let custom_items = item::get_custom_items_from_order(order.id, pool).await?;
+    if custom_items.is_empty() {
+        sqlx::query!(
+            "UPDATE order_bom SET bom_file_custom = NULL WHERE order_id = $1",
+            order.id
+        )
+        .execute(pool)
+        .await
+        .map_err(DataError::Query)?;
+        return Ok(());
+    }
+
+    let mut book = excel::create_custom_bom_file(pricing);
+    for custom_item in &custom_items {
+        excel::add_custom_item_to_bom(&mut book, custom_item, "".to_string(), pricing)
+            .map_err(DataError::FailedQuery)?;
+    }
+    let custom_bom_bytes = excel::save_to_bytes(&book).map_err(DataError::FailedQuery)?;
+    sqlx::query!(
+        "INSERT INTO order_bom (order_id, bom_file_custom, filename) VALUES ($1, $2, $3)
+         ON CONFLICT (order_id) DO UPDATE SET bom_file_custom = EXCLUDED.bom_file_custom, filename = EXCLUDED.filename",
+        order.id,
+        custom_bom_bytes,
+        order.description.replace(" ", "_").to_lowercase()
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
     Ok(())
 }
 
+fn describe_sourcing_errors(errors: &[&(DistributorKind, SourcingError)]) -> String {
+    errors
+        .iter()
+        .map(|(kind, e)| format!(
+            "{}: {}{}",
+            kind.name(),
+            e,
+            if e.is_retryable() { " (retry later)" } else { "" }
+        ))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Sources every item of the order and stores the per-distributor BOMs.
+/// With `force_refresh` cached distributor results are ignored and re-fetched.
+/// Items are sourced a few at a time, the requests to each distributor being limited by `sourcing`.
+/// Returns the searches that failed, so that they can be retried or the part numbers fixed.
+pub async fn generate_bom(pool: &PgPool, sourcing: &SourcingContext, order_id: i32, force_refresh: bool) -> Result<Vec<SourcingIssue>, DataError> {
+    println!("Generating BOM for order {}", order_id);
+    let order_items = item::get_items_from_order(order_id, pool).await?;
+    let distributors = distributor::enabled_distributors(sourcing);
+
+    let distributors_ref = &distributors;
+    let results: Vec<ItemProcessingResult> = stream::iter(order_items)
+        .map(|item| async move {
+            let (quotes, errors) = distributor::search_all(pool, sourcing, distributors_ref, &item, force_refresh).await;
+            ItemProcessingResult { item, quotes, errors }
+        })
+        .buffer_unordered(sourcing.max_concurrent_items)
+        .collect()
+        .await;
+
+    // allocations and sourcing results of the previous generation are replaced, with the cart and list built from them
+    item::clear_item_allocations(pool, order_id).await?;
+    item::clear_item_sourcing_results(pool, order_id).await?;
+    sqlx::query!(
+        "UPDATE orders SET mouser_cart_url = NULL, digikey_list_url = NULL WHERE id = $1",
+        order_id
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+
+    for result in &results {
+        for quote in &result.quotes {
+            println!("man: {} - id: {} - {}_price: {} - stock: {}",
+                result.item.manufacturer,
+                result.item.manufacturer_pn,
+                quote.distributor.name(),
+                quote.unit_price,
+                quote.availability);
+            item::add_item_sourcing_result(pool, order_id, result.item.manufacturer.clone(), result.item.manufacturer_pn.clone(), quote.distributor, Ok(quote)).await?;
+        }
+        for (kind, e) in &result.errors {
+            item::add_item_sourcing_result(pool, order_id, result.item.manufacturer.clone(), result.item.manufacturer_pn.clone(), *kind, Err(e)).await?;
+        }
+    }
+
+    // assign items to distributors minimising prices plus shipping of the whole order
+    let shipping_rules: HashMap<DistributorKind, ShippingRule> = distributors
+        .iter()
+        .map(|d| (d.kind(), ShippingRule::from_env(d.kind(), &sourcing.pricing)))
+        .collect();
+    let (items_quotes, items_errors): (Vec<_>, Vec<_>) = results
+        .into_iter()
+        .map(|result| ((result.item, result.quotes), result.errors))
+        .unzip();
+    // the search over distributor subsets is CPU bound, keep it off the async workers
+    let (items_quotes, plans, cost_summary) = tokio::task::spawn_blocking(move || {
+        let (plans, cost_summary) = optimiser::optimise_order(&items_quotes, &shipping_rules);
+        (items_quotes, plans, cost_summary)
+    })
+    .await
+    .map_err(|e| DataError::Internal(format!("Order optimisation failed: {}", e)))?;
+    println!("Order {} landed cost: {:.2} (per-item cheapest: {:.2}, saving {:.2})",
+        order_id,
+        cost_summary.total_cost,
+        cost_summary.greedy_total_cost,
+        cost_summary.saving);
+
+    let mut issues: Vec<SourcingIssue> = Vec::new();
+    for ((((item, quotes), offers), assignment), errors) in items_quotes.iter().zip(&plans).zip(&cost_summary.items).zip(&items_errors) {
+        let mut notes: Vec<String> = Vec::new();
+        if let Some(pinned) = item.pinned_distributor() {
+            notes.push(match item.search_pn() == item.manufacturer_pn {
+                true => format!("Pinned to {}", pinned.name()),
+                false => format!("Pinned to {} {}", pinned.name(), item.search_pn()),
+            });
+        }
+        if assignment.distributors != assignment.greedy_distributors && !assignment.greedy_distributors.is_empty() {
+            notes.push(format!(
+                "Moved from {} to lower the shipping costs of the order",
+                assignment.greedy_distributors.iter().map(|kind| kind.name()).collect::<Vec<&str>>().join(" + ")
+            ));
+        }
+
+        // a missing quote only matters for the team when it is not a plain "not sold here" or the item could not be bought at all
+        let relevant_errors: Vec<&(DistributorKind, SourcingError)> = errors
+            .iter()
+            .filter(|(_, e)| offers.is_empty() || !matches!(e, SourcingError::NotFound))
+            .collect();
+        if offers.is_empty() { // part not found or not available in the requested quantity
+            println!("man: {} - id: {} - not available on any distributor", item.manufacturer, item.manufacturer_pn);
+            if !quotes.is_empty() {
+                notes.push(format!(
+                    "Not enough stock: {}",
+                    quotes.iter().map(|q| format!("{} {}", q.distributor.name(), q.availability)).collect::<Vec<String>>().join(", ")
+                ));
+            }
+            if !relevant_errors.is_empty() {
+                notes.push(format!("Not sourced - {}", describe_sourcing_errors(&relevant_errors)));
+            }
+        } else if !relevant_errors.is_empty() {
+            notes.push(format!("Not compared with {}", describe_sourcing_errors(&relevant_errors)));
+        }
+        issues.extend(relevant_errors.iter().map(|(kind, e)| SourcingIssue {
+            manufacturer: item.manufacturer.clone(),
+            manufacturer_pn: item.manufacturer_pn.clone(),
+            distributor: *kind,
+            error: e.kind().to_string(),
+            message: e.to_string(),
+            retryable: e.is_retryable(),
+        }));
+
+        let item_notes = match notes.is_empty() {
+            true => None,
+            false => Some(notes.join("; ")),
+        };
+        store_item_offers(pool, order_id, item, offers, item_notes).await?;
+    }
+    let cost_summary_json = serde_json::to_string(&cost_summary)
+        .map_err(|e| DataError::Internal(e.to_string()))?;
+    sqlx::query!(
+        "INSERT INTO order_bom (order_id, cost_summary) VALUES ($1, $2)
+         ON CONFLICT (order_id) DO UPDATE SET cost_summary = EXCLUDED.cost_summary",
+        order_id,
+        cost_summary_json
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| DataError::Query(e))?;
+
+    // the workbooks are derived from the stored offers
+    save_bom_files(pool, &sourcing.pricing, order_id).await?;
+
+    Ok(issues)
+}
+
+/// Landed cost summary of the last BOM generation, if the order has one.
+pub async fn get_cost_summary(pool: &PgPool, order_id: i32) -> Result<Option<CostSummary>, DataError> {
+    let record = sqlx::query!(
+        "SELECT cost_summary FROM order_bom WHERE order_id = $1",
+        order_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DataError::Query)?;
+
+    let summary: Option<CostSummary> = match record.and_then(|r| r.cost_summary) {
+        Some(json) => Some(serde_json::from_str(&json).map_err(|e| DataError::Internal(e.to_string()))?),
+        None => None,
+    };
+
+    // custom items can change after the BOM generation, their totals are always computed again
+    let custom_vendors = custom_vendor_totals(&item::get_custom_items_from_order(order_id, pool).await?);
+    match (summary, custom_vendors.is_empty()) {
+        (Some(summary), _) => Ok(Some(summary.with_custom_vendors(custom_vendors))),
+        (None, false) => Ok(Some(CostSummary {
+            distributors: Vec::new(),
+            items: Vec::new(),
+            total_cost: 0.0,
+            greedy_total_cost: 0.0,
+            saving: 0.0,
+            custom_vendors: Vec::new(),
+        }.with_custom_vendors(custom_vendors))),
+        (None, true) => Ok(None),
+    }
+}
+
+fn custom_vendor_totals(custom_items: &[CustomOrderItem]) -> Vec<CustomVendorTotal> {
+    let mut totals: Vec<CustomVendorTotal> = Vec::new();
+    for custom_item in custom_items {
+        match totals.iter_mut().find(|total| total.vendor == custom_item.vendor) {
+            Some(total) => {
+                total.subtotal += custom_item.extended_price();
+                total.shipping += custom_item.shipping;
+            },
+            None => totals.push(CustomVendorTotal {
+                vendor: custom_item.vendor.clone(),
+                subtotal: custom_item.extended_price(),
+                shipping: custom_item.shipping,
+            }),
+        }
+    }
+    totals
+}
+
 pub async fn create_order_from_kicad_bom(
     pool: &PgPool,
     author_id: i32,
@@ -474,7 +821,10 @@ pub async fn create_order_from_kicad_bom(
     let bom_items = excel::parse_kicad_bom_file(kicad_bom_file).map_err(|e| DataError::FailedQuery(e))?;
     for item in bom_items {
         println!("{}: {}x {}", item.manifacturer, item.quantity, item.manifacturer_pn);
-        add_item_to_order(pool, order_id, item.manifacturer, item.manifacturer_pn, item.quantity, proposal.clone(), project.clone(), None, None).await?;
+        add_item_to_order(pool, order_id, &NewOrderItem::new(item.manifacturer.clone(), item.manifacturer_pn.clone(), item.quantity, proposal.clone(), project.clone())).await?;
+        if let Some(reference_designators) = item.reference_designators {
+            item::add_item_reference_designators(pool, order_id, &item.manifacturer, &item.manifacturer_pn, &reference_designators).await?;
+        }
     }
     Ok(())
 }
@@ -490,7 +840,10 @@ pub async fn bulk_add_from_bom(
     let bom_items = excel::parse_kicad_bom_file(bom).map_err(|e| DataError::FailedQuery(e))?;
     for item in bom_items {
         println!("{}: {}x {}", item.manifacturer, item.quantity, item.manifacturer_pn);
-        add_item_to_order(pool, order_id, item.manifacturer, item.manifacturer_pn, item.quantity, proposal.clone(), project.clone(), None, None).await?;
+        add_item_to_order(pool, order_id, &NewOrderItem::new(item.manifacturer.clone(), item.manifacturer_pn.clone(), item.quantity, proposal.clone(), project.clone())).await?;
+        if let Some(reference_designators) = item.reference_designators {
+            item::add_item_reference_designators(pool, order_id, &item.manifacturer, &item.manifacturer_pn, &reference_designators).await?;
+        }
     }
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use sqlx::PgPool;
+use crate::data::errors::DataError;
+use crate::models::distributor_models::DistributorQuote;
+use crate::models::price_history::{PriceObservation, PriceTrend};
+
+/// Records the price and stock of a quote returned by a live distributor search.
+pub async fn record_observation(pool: &PgPool, quote: &DistributorQuote, quantity: u32) -> Result<(), DataError> {
+    let price_breaks = serde_json::to_string(&quote.price_breaks)
+        .map_err(|e| DataError::Internal(e.to_string()))?;
+    sqlx::query!(
+        "INSERT INTO price_observations (distributor, manufacturer, manufacturer_pn, distributor_pn, quantity, unit_price, availability, price_breaks)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        quote.distributor.name(),
+        quote.manufacturer,
+        quote.manufacturer_pn,
+        quote.distributor_pn,
+        quantity.min(i32::MAX as u32) as i32,
+        quote.unit_price,
+        quote.availability.min(i32::MAX as u32) as i32,
+        price_breaks
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+/// Every observation of a part, oldest first. Without a manufacturer all the parts sharing the part number are returned.
+pub async fn get_price_history(pool: &PgPool, manufacturer: Option<&str>, manufacturer_pn: &str) -> Result<Vec<PriceObservation>, DataError> {
+    let observations = sqlx::query_as!(
+        PriceObservation,
+        r#"SELECT distributor, manufacturer, manufacturer_pn, distributor_pn, quantity, unit_price, availability,
+            observed_at as "observed_at: chrono::DateTime<chrono::Utc>"
+         FROM price_observations
+         WHERE manufacturer_pn = $1 AND ($2::TEXT IS NULL OR LOWER(manufacturer) = LOWER($2))
+         ORDER BY observed_at, id"#,
+        manufacturer_pn,
+        manufacturer
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(observations)
+}
+
+/// First and latest price of each distributor in `observations` (which must be sorted oldest first).
+/// The unit price depends on the price break reached, so only observations at the same quantity are compared.
+pub fn price_trends(observations: &[PriceObservation]) -> Vec<PriceTrend> {
+    let mut trends: Vec<PriceTrend> = Vec::new();
+    for observation in observations {
+        match trends.iter_mut().find(|t| t.distributor == observation.distributor && t.quantity == observation.quantity) {
+            Some(trend) => {
+                trend.latest_price = observation.unit_price;
+                trend.latest_seen = observation.get_date();
+                trend.observations += 1;
+            },
+            None => trends.push(PriceTrend {
+                distributor: observation.distributor.clone(),
+                quantity: observation.quantity,
+                first_price: observation.unit_price,
+                first_seen: observation.get_date(),
+                latest_price: observation.unit_price,
+                latest_seen: observation.get_date(),
+                change_percent: 0.0,
+                observations: 1,
+            }),
+        }
+    }
+    for trend in &mut trends {
+        if trend.first_price > 0.0 {
+            trend.change_percent = (trend.latest_price - trend.first_price) / trend.first_price * 100.0;
+        }
+    }
+    trends
+}
@@ -0,0 +1,127 @@
+use sqlx::{PgConnection, PgPool, types::time::Date};
+use crate::data::{errors::DataError, inventory, order};
+use crate::models::inventory::InventoryAdjustment;
+use crate::models::item::OrderItem;
+use crate::models::order_status::{OrderStatus, OrderTransition};
+use crate::models::receiving::ItemReceipt;
+
+/// Records a delivery of an order item and adds it to the lab inventory, then rolls the order status up,
+/// all in one transaction. The BOM files have to be saved again afterwards for their "Delivered" column to be up to date.
+pub async fn record_receipt(
+    pool: &PgPool,
+    order_id: i32,
+    manufacturer: String,
+    manufacturer_pn: String,
+    quantity: i32,
+    received_on: Date,
+    received_by: i32,
+) -> Result<(), DataError> {
+    if quantity <= 0 {
+        return Err(DataError::Internal(format!("Invalid received quantity {}", quantity)));
+    }
+    let mut transaction = pool.begin().await.map_err(DataError::Query)?;
+    // the receipt and the item total are written by the same statement, so they cannot diverge
+    let result = sqlx::query!(
+        "WITH receipt AS (
+            INSERT INTO item_receipts (order_id, manufacturer, manufacturer_pn, quantity, received_on, received_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING order_id, manufacturer, manufacturer_pn, quantity
+         )
+         UPDATE order_items i SET received_quantity = i.received_quantity + r.quantity
+         FROM receipt r
+         WHERE i.order_id = r.order_id AND i.manufacturer = r.manufacturer AND i.manufacturer_pn = r.manufacturer_pn",
+        order_id,
+        manufacturer,
+        manufacturer_pn,
+        quantity,
+        received_on,
+        received_by
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(DataError::Query)?;
+    if result.rows_affected() == 0 {
+        return Err(DataError::Internal(format!("Order {} has no item {} {}", order_id, manufacturer, manufacturer_pn)));
+    }
+    let adjustment = InventoryAdjustment {
+        manufacturer,
+        manufacturer_pn,
+        location: inventory::default_location(),
+        change: quantity,
+        reason: format!("Received with order #{}", order_id),
+    };
+    inventory::adjust_stock(&mut transaction, &adjustment, Some(order_id), Some(received_by)).await?;
+    roll_up_order_status(&mut transaction, order_id).await?;
+    transaction.commit().await.map_err(DataError::Query)?;
+    Ok(())
+}
+
+/// Moves a purchased or shipped order to partially received once something was delivered,
+/// and to received once every item was delivered in full.
+/// Custom items are not tracked: an order with custom items stays partially received until it is marked as received by hand.
+async fn roll_up_order_status(conn: &mut PgConnection, order_id: i32) -> Result<(), DataError> {
+    // same rule as `OrderItem::is_received`
+    let record = sqlx::query!(
+        r#"SELECT o.status,
+            COALESCE(BOOL_AND(i.received_quantity >= COALESCE(i.purchase_quantity, i.quantity)), FALSE)
+                AND NOT EXISTS (SELECT 1 FROM custom_order_items c WHERE c.order_id = o.id) AS "all_received!",
+            COALESCE(BOOL_OR(i.received_quantity > 0), FALSE) AS "any_received!"
+         FROM orders o
+         LEFT JOIN order_items i ON i.order_id = o.id
+         WHERE o.id = $1
+         GROUP BY o.id"#,
+        order_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DataError::Query)?;
+    let transition = if record.all_received {
+        OrderTransition::Receive
+    } else if record.any_received {
+        OrderTransition::ReceivePartially
+    } else {
+        return Ok(());
+    };
+    let status = OrderStatus::from_name(&record.status);
+    if status.is_some_and(|status| transition.from_statuses().contains(&status)) {
+        order::apply_transition(conn, order_id, transition).await?;
+    }
+    Ok(())
+}
+
+pub async fn get_receipts_for_order(pool: &PgPool, order_id: i32) -> Result<Vec<ItemReceipt>, DataError> {
+    let receipts = sqlx::query_as!(
+        ItemReceipt,
+        r#"SELECT r.id, r.order_id, r.manufacturer, r.manufacturer_pn, r.quantity,
+            r.received_on,
+            u.username AS received_by,
+            r.created_at as "created_at: chrono::DateTime<chrono::Utc>"
+         FROM item_receipts r
+         JOIN users u ON u.id = r.received_by
+         WHERE r.order_id = $1
+         ORDER BY r.received_on, r.id"#,
+        order_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(receipts)
+}
+
+/// Text of the "Delivered" BOM column: empty until something arrives, then the received quantity and the date of the last delivery.
+pub fn delivered_text(item: &OrderItem, receipts: &[ItemReceipt]) -> String {
+    let last_receipt = receipts
+        .iter()
+        .filter(|receipt| receipt.manufacturer == item.manufacturer && receipt.manufacturer_pn == item.manufacturer_pn)
+        .max_by_key(|receipt| receipt.received_on);
+    match last_receipt {
+        None => "".to_string(),
+        Some(receipt) if item.is_received() => format!("Yes ({})", receipt.get_date()),
+        Some(receipt) => format!(
+            "{} of {} ({})",
+            item.received_quantity,
+            item.expected_quantity(),
+            receipt.get_date()
+        ),
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::HashMap, time::Duration};
+use dotenvy::dotenv;
+use reqwest::Client;
+use tokio::{sync::{Mutex, Semaphore, SemaphorePermit}, time::{sleep_until, Instant}};
+use crate::data::errors::SourcingError;
+use crate::models::{distributor_models::DistributorKind, pricing::PricingSettings};
+
+/// Reads a credential or other setting a distributor cannot work without.
+pub fn required_env(name: &str) -> Result<String, SourcingError> {
+    dotenv().ok();
+    std::env::var(name).map_err(|_| SourcingError::Config(format!("{} must be set", name)))
+}
+
+/// Limits the requests sent to a single distributor: at most `max_concurrent` in flight
+/// and no more than `requests_per_second` started every second.
+pub struct RequestLimiter {
+    semaphore: Semaphore,
+    min_interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+}
+
+impl RequestLimiter {
+    pub fn new(max_concurrent: usize, requests_per_second: f64) -> RequestLimiter {
+        RequestLimiter {
+            semaphore: Semaphore::new(max_concurrent.max(1)),
+            min_interval: match requests_per_second > 0.0 {
+                // at least one request per hour, tiny rates would overflow the interval
+                true => Some(Duration::from_secs_f64(1.0 / requests_per_second.max(1.0 / 3600.0))),
+                false => None,
+            },
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for a free slot; the request may be sent while the returned permit is alive.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self.semaphore
+            .acquire()
+            .await
+            .expect("request limiter semaphore is never closed");
+        if let Some(interval) = self.min_interval {
+            let slot = {
+                let mut next_slot = self.next_slot.lock().await;
+                let slot = (*next_slot).max(Instant::now());
+                *next_slot = slot + interval;
+                slot
+            };
+            sleep_until(slot).await;
+        }
+        permit
+    }
+}
+
+/// HTTP client and request limits shared by every distributor search, held in `AppState`.
+pub struct SourcingContext {
+    pub client: Client,
+    limiters: HashMap<DistributorKind, RequestLimiter>,
+    pub max_concurrent_items: usize,
+    pub pricing: PricingSettings, // currency and locale of the requests
+    enabled: Vec<DistributorKind>,
+}
+
+/// Environment variables a distributor's API cannot be called without.
+fn credential_vars(kind: DistributorKind) -> &'static [&'static str] {
+    match kind {
+        DistributorKind::Mouser => &["MOUSER_API_KEY"],
+        DistributorKind::DigiKey => &["DIGIKEY_CLIENT_ID", "DIGIKEY_CLIENT_SECRET"],
+        DistributorKind::Farnell => &["FARNELL_API_KEY"],
+        DistributorKind::Lcsc => &[],
+        DistributorKind::Tme => &["TME_TOKEN", "TME_APP_SECRET"],
+    }
+}
+
+impl SourcingContext {
+    /// Limits are read from `<DISTRIBUTOR>_MAX_CONCURRENT_REQUESTS` and `<DISTRIBUTOR>_REQUESTS_PER_SECOND`
+    /// (e.g. `MOUSER_REQUESTS_PER_SECOND`), defaulting to values within each distributor's API quota.
+    /// A rate of 0 disables the requests-per-second limit.
+    pub fn from_env() -> SourcingContext {
+        dotenv().ok();
+        let kinds = [
+            DistributorKind::Mouser,
+            DistributorKind::DigiKey,
+            DistributorKind::Farnell,
+            DistributorKind::Lcsc,
+            DistributorKind::Tme,
+        ];
+        let limiters = kinds
+            .into_iter()
+            .map(|kind| {
+                let (default_concurrent, default_rate) = match kind {
+                    DistributorKind::Mouser => (1, 0.5), // 30 calls per minute
+                    DistributorKind::DigiKey => (4, 2.0), // 120 calls per minute
+                    DistributorKind::Farnell => (2, 2.0),
+                    DistributorKind::Lcsc => (2, 1.0),
+                    DistributorKind::Tme => (4, 5.0),
+                };
+                let prefix = kind.name().to_uppercase();
+                let max_concurrent = std::env::var(format!("{}_MAX_CONCURRENT_REQUESTS", prefix))
+                    .ok()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or(default_concurrent);
+                let requests_per_second = std::env::var(format!("{}_REQUESTS_PER_SECOND", prefix))
+                    .ok()
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .unwrap_or(default_rate);
+                (kind, RequestLimiter::new(max_concurrent, requests_per_second))
+            })
+            .collect();
+
+        // distributors without credentials are left out of every search instead of failing each item
+        let enabled = kinds
+            .into_iter()
+            .filter(|kind| {
+                let missing: Vec<&str> = credential_vars(*kind)
+                    .iter()
+                    .copied()
+                    .filter(|name| std::env::var(name).unwrap_or_default().is_empty())
+                    .collect();
+                if !missing.is_empty() {
+                    println!("{} disabled: {} not set", kind.name(), missing.join(", "));
+                }
+                missing.is_empty()
+            })
+            .collect();
+
+        let client = Client::builder()
+            .pool_idle_timeout(Duration::from_secs(90))
+            .timeout(Duration::from_secs(100))
+            .build()
+            .expect("Failed to build the HTTP client");
+
+        SourcingContext {
+            client,
+            limiters,
+            max_concurrent_items: std::env::var("SOURCING_MAX_CONCURRENT_ITEMS")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .unwrap_or(8)
+                .max(1),
+            pricing: PricingSettings::from_env(),
+            enabled,
+        }
+    }
+
+    /// Whether the credentials of `distributor` are configured.
+    pub fn is_enabled(&self, distributor: DistributorKind) -> bool {
+        self.enabled.contains(&distributor)
+    }
+
+    /// Waits until a request to `distributor` may be sent, see `RequestLimiter::acquire`.
+    pub async fn acquire(&self, distributor: DistributorKind) -> Option<SemaphorePermit<'_>> {
+        match self.limiters.get(&distributor) {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::{sync::Arc, time::Duration};
+use dotenvy::dotenv;
+use sqlx::PgPool;
+use crate::data::{distributor, errors::DataError, item, mail, notification, sourcing::SourcingContext};
+use crate::models::distributor_models::{DistributorQuote, Offer};
+use crate::models::order_status::OrderStatus;
+use crate::models::stock_watch::StockWatch;
+
+/// Puts a watch on an order item. Watching an item again re-arms a watch that already notified.
+pub async fn add_stock_watch(
+    pool: &PgPool,
+    order_id: i32,
+    manufacturer: String,
+    manufacturer_pn: String,
+    created_by: i32,
+) -> Result<(), DataError> {
+    sqlx::query!(
+        "INSERT INTO stock_watches (order_id, manufacturer, manufacturer_pn, created_by) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (order_id, manufacturer, manufacturer_pn)
+         DO UPDATE SET created_by = EXCLUDED.created_by, created_at = NOW(), last_checked_at = NULL, notified_at = NULL",
+        order_id,
+        manufacturer,
+        manufacturer_pn,
+        created_by
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+/// Removes a watch of the order. Only who created it, the author of the order and the board can remove it.
+pub async fn delete_stock_watch(pool: &PgPool, order_id: i32, watch_id: i32, user_id: i32, is_board: bool) -> Result<(), DataError> {
+    sqlx::query!(
+        "DELETE FROM stock_watches w USING orders o
+         WHERE w.id = $1 AND w.order_id = $2 AND o.id = w.order_id AND ($4 OR w.created_by = $3 OR o.author_id = $3)",
+        watch_id,
+        order_id,
+        user_id,
+        is_board
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+pub async fn get_watches_for_order(pool: &PgPool, order_id: i32) -> Result<Vec<StockWatch>, DataError> {
+    let watches = sqlx::query_as!(
+        StockWatch,
+        r#"SELECT w.id, w.order_id, w.manufacturer, w.manufacturer_pn, i.quantity, o.author_id, o.description AS order_description,
+            w.created_at as "created_at: chrono::DateTime<chrono::Utc>",
+            w.last_checked_at as "last_checked_at: chrono::DateTime<chrono::Utc>",
+            w.notified_at as "notified_at: chrono::DateTime<chrono::Utc>"
+         FROM stock_watches w
+         JOIN order_items i ON i.order_id = w.order_id AND i.manufacturer = w.manufacturer AND i.manufacturer_pn = w.manufacturer_pn
+         JOIN orders o ON o.id = w.order_id
+         WHERE w.order_id = $1
+         ORDER BY w.created_at"#,
+        order_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(watches)
+}
+
+/// Watches that have not notified yet, on items still to be delivered of orders still in progress.
+pub async fn get_pending_watches(pool: &PgPool) -> Result<Vec<StockWatch>, DataError> {
+    let finished_statuses: Vec<String> = OrderStatus::finished_statuses()
+        .iter()
+        .map(|status| status.name().to_string())
+        .collect();
+    let watches = sqlx::query_as!(
+        StockWatch,
+        r#"SELECT w.id, w.order_id, w.manufacturer, w.manufacturer_pn, i.quantity, o.author_id, o.description AS order_description,
+            w.created_at as "created_at: chrono::DateTime<chrono::Utc>",
+            w.last_checked_at as "last_checked_at: chrono::DateTime<chrono::Utc>",
+            w.notified_at as "notified_at: chrono::DateTime<chrono::Utc>"
+         FROM stock_watches w
+         JOIN order_items i ON i.order_id = w.order_id AND i.manufacturer = w.manufacturer AND i.manufacturer_pn = w.manufacturer_pn
+         JOIN orders o ON o.id = w.order_id
+         WHERE w.notified_at IS NULL AND o.status <> ALL($1)
+         AND i.received_quantity < COALESCE(i.purchase_quantity, i.quantity)
+         ORDER BY w.last_checked_at NULLS FIRST"#,
+        &finished_statuses
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(watches)
+}
+
+async fn set_watch_checked(pool: &PgPool, watch_id: i32, back_in_stock: bool) -> Result<(), DataError> {
+    sqlx::query!(
+        "UPDATE stock_watches SET last_checked_at = NOW(), notified_at = CASE WHEN $2 THEN NOW() ELSE notified_at END WHERE id = $1",
+        watch_id,
+        back_in_stock
+    )
+    .execute(pool)
+    .await
+    .map_err(DataError::Query)?;
+    Ok(())
+}
+
+/// Offers covering the required quantity, from a single distributor when possible.
+fn available_offers(quotes: &[DistributorQuote], quantity: u32) -> Option<Vec<Offer>> {
+    match distributor::select_best_offer(quotes, quantity) {
+        Some(offer) => Some(vec![offer]),
+        None => distributor::split_across_distributors(quotes, quantity),
+    }
+}
+
+/// Re-queries the distributors for the watched item and notifies the order author when the
+/// required quantity can be bought. Returns whether the item is back in stock.
+/// `force_refresh` bypasses the distributor cache, for checks asked by a user.
+pub async fn check_watch(pool: &PgPool, sourcing: &SourcingContext, watch: &StockWatch, force_refresh: bool) -> Result<bool, DataError> {
+    let order_item = match item::get_item(pool, watch.order_id, &watch.manufacturer, &watch.manufacturer_pn).await? {
+        Some(order_item) => order_item,
+        None => return Ok(false),
+    };
+    let distributors = distributor::enabled_distributors(sourcing);
+    let (quotes, _) = distributor::search_all(pool, sourcing, &distributors, &order_item, force_refresh).await;
+    let offers = available_offers(&quotes, order_item.quantity.max(0) as u32);
+
+    if let Some(offers) = &offers {
+        notify_back_in_stock(pool, watch, offers).await?;
+    }
+    set_watch_checked(pool, watch.id, offers.is_some()).await?;
+    Ok(offers.is_some())
+}
+
+async fn notify_back_in_stock(pool: &PgPool, watch: &StockWatch, offers: &[Offer]) -> Result<(), DataError> {
+    let message = format!(
+        "{} {} (order #{}: {}) is back in stock for the {} pieces required: {}",
+        watch.manufacturer,
+        watch.manufacturer_pn,
+        watch.order_id,
+        watch.order_description,
+        watch.quantity,
+        offers
+            .iter()
+            .map(|offer| format!("{} {} in stock", offer.quote.distributor.name(), offer.quote.availability))
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+    let link = format!("/orders/{}/edit", watch.order_id);
+    notification::add_notification(pool, watch.author_id, message.clone(), Some(link)).await?;
+
+    let email = sqlx::query!(
+        "SELECT email FROM users WHERE id = $1",
+        watch.author_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DataError::Query)?
+    .and_then(|user| user.email)
+    .filter(|email| !email.is_empty());
+    // the in-app notification is enough when the email cannot be sent
+    if let Some(email) = email {
+        let subject = format!("PoliTOcean: {} back in stock", watch.manufacturer_pn);
+        let text = format!("{}\n\nGenerate the BOM of the order again to include it.", message);
+        if let Err(e) = mail::send_text_mail(&email, &subject, text).await {
+            println!("Failed sending back-in-stock email for watch {}: {}", watch.id, e);
+        }
+    }
+    Ok(())
+}
+
+fn check_interval() -> Duration {
+    dotenv().ok();
+    let minutes = std::env::var("STOCK_WATCH_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .unwrap_or(360);
+    Duration::from_secs(minutes.max(1) * 60)
+}
+
+/// Background task checking every pending watch each `STOCK_WATCH_INTERVAL_MINUTES` (6 hours by default).
+pub async fn run_stock_watches(pool: PgPool, sourcing: Arc<SourcingContext>) {
+    let interval = check_interval();
+    loop {
+        tokio::time::sleep(interval).await;
+        let watches = match get_pending_watches(&pool).await {
+            Ok(watches) => watches,
+            Err(e) => {
+                println!("Failed loading stock watches: {}", e);
+                continue;
+            }
+        };
+        for watch in &watches {
+            match check_watch(&pool, &sourcing, watch, false).await {
+                Ok(true) => println!("{} {} of order {} is back in stock", watch.manufacturer, watch.manufacturer_pn, watch.order_id),
+                Ok(false) => {},
+                Err(e) => println!("Failed checking stock watch {}: {}", watch.id, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+use dotenvy::dotenv;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::de::DeserializeOwned;
+use sha1::Sha1;
+use crate::models::tme_api_models::{PricesAndStocksData, Product, SearchData, TmeResponse};
+use crate::data::{distributor, errors::SourcingError, sourcing::{self, SourcingContext}};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, QuotePriceBreak};
+use serde_path_to_error::deserialize;
+
+type HmacSha1 = Hmac<Sha1>;
+
+// RFC 3986 unreserved characters are the only ones TME expects unencoded in the signature base
+const RFC3986: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, RFC3986).to_string()
+}
+
+/// Calls a TME API action, signing the request with HMAC-SHA1 as described in the TME API docs.
+async fn tme_post<T: DeserializeOwned>(
+    sourcing: &SourcingContext,
+    action: &str,
+    mut params: Vec<(String, String)>,
+) -> Result<T, SourcingError> {
+    let token = sourcing::required_env("TME_TOKEN")?;
+    let app_secret = sourcing::required_env("TME_APP_SECRET")?;
+
+    let url = format!("{}/{}.json", DistributorKind::Tme.api_base_url(), action);
+    params.push(("Token".to_string(), token));
+    params.sort();
+
+    let encoded_params = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<String>>()
+        .join("&");
+    let signature_base = format!("POST&{}&{}", encode(&url), encode(&encoded_params));
+
+    let mut mac = HmacSha1::new_from_slice(app_secret.as_bytes())
+        .map_err(|e| SourcingError::Config(format!("Invalid TME_APP_SECRET: {}", e)))?;
+    mac.update(signature_base.as_bytes());
+    let signature = STANDARD.encode(mac.finalize().into_bytes());
+
+    let _permit = sourcing.acquire(DistributorKind::Tme).await;
+    let response = sourcing.client
+        .post(&url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("accept", "application/json")
+        .body(format!("{}&ApiSignature={}", encoded_params, encode(&signature)))
+        .timeout(Duration::from_secs(100))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(SourcingError::from_status(response.status(), &format!("Failed to call TME {}", action)));
+    }
+
+    let bytes = response.bytes().await?;
+    let mut de = serde_json::Deserializer::from_slice(&bytes);
+    match deserialize::<_, TmeResponse<T>>(&mut de) {
+        Ok(result) if result.status == "OK" => {
+            result.data.ok_or_else(|| SourcingError::Parse(format!("TME {} returned no data", action)))
+        },
+        // e.g. E_AUTHORIZATION_FAILED, E_INVALID_SIGNATURE, E_TOO_MANY_REQUESTS
+        Ok(result) => {
+            let message = format!("TME {} returned status {}", action, result.status);
+            Err(if result.status.contains("AUTH") || result.status.contains("SIGNATURE") || result.status.contains("TOKEN") {
+                SourcingError::Auth(message)
+            } else if result.status.contains("TOO_MANY") || result.status.contains("LIMIT") {
+                SourcingError::Quota(message)
+            } else {
+                SourcingError::Http(message)
+            })
+        },
+        Err(e) => {
+            eprintln!("❌ Path error: {}", e);
+            Err(SourcingError::Parse(e.to_string()))
+        }
+    }
+}
+
+/// Country and currency of the requests, `TME_COUNTRY` and `TME_CURRENCY` override the pricing settings.
+pub fn locale(sourcing: &SourcingContext) -> (String, String) {
+    dotenv().ok();
+    let country = std::env::var("TME_COUNTRY").unwrap_or(sourcing.pricing.country.clone());
+    let currency = std::env::var("TME_CURRENCY").unwrap_or(sourcing.pricing.currency.clone());
+    (country, currency)
+}
+
+pub async fn search_tme(
+    sourcing: &SourcingContext,
+    query_manufacturer: &str,
+    query_manufacturer_pn: &str,
+    quantity: u32,
+) -> Result<Option<DistributorQuote>, SourcingError> {
+    dotenv().ok();
+    let (country, currency) = locale(sourcing);
+    let language = std::env::var("TME_LANGUAGE").unwrap_or(sourcing.pricing.language.to_uppercase());
+    println!("Searching for {} {} on TME", query_manufacturer, query_manufacturer_pn);
+
+    let search: SearchData = tme_post(sourcing, "Products/Search", vec![
+        ("Country".to_string(), country.clone()),
+        ("Language".to_string(), language.clone()),
+        ("SearchPlain".to_string(), query_manufacturer_pn.to_string()),
+    ]).await?;
+
+    let similar_parts: Vec<String> = search.product_list
+        .iter()
+        .filter_map(|product| product.original_symbol.clone())
+        .take(5)
+        .collect();
+
+    // assure we return only the requested item
+    let products: Vec<Product> = search.product_list
+        .into_iter()
+        .filter(|product| {
+            product.symbol == query_manufacturer_pn
+                || product.original_symbol
+                    .as_deref()
+                    .is_some_and(|pn| pn.eq_ignore_ascii_case(query_manufacturer_pn))
+        })
+        .collect();
+
+    if products.is_empty() {
+        return match similar_parts.is_empty() {
+            true => Ok(None),
+            false => Err(SourcingError::Ambiguous(similar_parts)),
+        };
+    }
+
+    let mut price_params = vec![
+        ("Country".to_string(), country),
+        ("Currency".to_string(), currency),
+        ("Language".to_string(), language),
+    ];
+    for (index, product) in products.iter().enumerate() {
+        price_params.push((format!("SymbolList[{}]", index), product.symbol.clone()));
+    }
+    let prices: PricesAndStocksData = tme_post(sourcing, "Products/GetPricesAndStocks", price_params).await?;
+
+    let quotes: Vec<DistributorQuote> = products
+        .into_iter()
+        .filter_map(|product| {
+            let product_prices = prices.product_list.iter().find(|p| p.symbol == product.symbol)?;
+            let mut quote = DistributorQuote {
+                distributor: DistributorKind::Tme,
+                manufacturer: product.producer.unwrap_or_default(),
+                manufacturer_pn: product.original_symbol.unwrap_or_default(),
+                description: product.description.unwrap_or_default(),
+                distributor_pn: product.symbol,
+                product_url: product.product_information_page
+                    .map(|page| if page.starts_with("//") { format!("https:{}", page) } else { page })
+                    .unwrap_or_default(),
+                unit_price: 0.0,
+                availability: product_prices.amount,
+                minimum_order_quantity: product.min_amount.unwrap_or(1),
+                price_breaks: product_prices.price_list
+                    .iter()
+                    .map(|price| QuotePriceBreak {
+                        quantity: price.amount,
+                        unit_price: price.price_value,
+                    })
+                    .collect(),
+            };
+            quote.unit_price = quote.unit_price_for(quantity).unwrap_or(0.0);
+            Some(quote)
+        })
+        .collect();
+
+    Ok(distributor::pick_offer(quotes, quantity))
+}
@@ -2,10 +2,10 @@ use crate::models::templates::AdvisorHomepageTemplate;
 use askama::Template;
 use crate::{
     models::app::AppState,
-    data::{errors, order},
+    data::{errors, notification, order},
 };
 use axum::{
-    extract::State, response::{Html, IntoResponse, Redirect, Response}
+    extract::{Path, State}, response::{Html, IntoResponse, Redirect, Response}
 };
 use tower_sessions::Session;
 
@@ -22,6 +22,7 @@ pub async fn advisors_homepage_handler(
             // if user is logged in, get the user's orders, render them in a table
             let html_string = AdvisorHomepageTemplate {
                 orders: order::get_order_from_author_id(id, &app_state.connection_pool).await?,
+                notifications: notification::get_unread_notifications(&app_state.connection_pool, id).await?,
             }.render().unwrap();
             Ok(Html(html_string).into_response())
         }
@@ -31,3 +32,17 @@ pub async fn advisors_homepage_handler(
         }
     }
 }
+
+pub async fn mark_notification_read_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Path(notification_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    let user_id = session.get::<i32>("authenticated_user_id")
+    .await
+    .map_err(errors::AppError::Session)?;
+    if let Some(id) = user_id {
+        notification::mark_notification_read(&app_state.connection_pool, notification_id, id).await?;
+    }
+    Ok(Redirect::to("/home").into_response())
+}
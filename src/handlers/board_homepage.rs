@@ -24,7 +24,9 @@ pub async fn board_homepage_handler(
                     return Ok(Redirect::to("/home").into_response());
                 } else {
                     let html_string = BoardHomepageTemplate {
-                        orders: order::get_ready_orders(&app_state.connection_pool).await?,
+                        orders: order::get_submitted_orders(&app_state.connection_pool).await?,
+                        budgets: data::budget::get_budget_overview(&app_state.connection_pool).await?,
+                        warning: session.remove::<String>("approval_warning").await.map_err(errors::AppError::Session)?,
                     }.render().unwrap();
                     return Ok(Html(html_string).into_response());
                 }
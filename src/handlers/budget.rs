@@ -0,0 +1,46 @@
+use axum::{
+    extract::State, response::{IntoResponse, Redirect, Response}, Form, Json
+};
+use tower_sessions::Session;
+use crate::{
+    data::{budget, errors, user},
+    models::{app::AppState, budget::{BudgetForm, BudgetOverview}},
+};
+
+pub async fn budgets_json_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<BudgetOverview>, errors::AppError> {
+    Ok(Json(budget::get_budget_overview(&app_state.connection_pool).await?))
+}
+
+/// Only the board and the professor can change budgets, they are sent back to their homepage.
+pub async fn set_budget_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Form(form): Form<BudgetForm>,
+) -> Result<Response, errors::AppError> {
+    let user_id = session.get::<i32>("authenticated_user_id")
+    .await
+    .map_err(errors::AppError::Session)?;
+    let user_role = match user_id {
+        Some(id) => user::get_user_role(&app_state.connection_pool, id).await?,
+        None => return Ok(Redirect::to("/").into_response()),
+    };
+    let homepage = match user_role.as_str() {
+        "board" => "/board/home",
+        "prof" => "/prof",
+        _ => return Ok(Redirect::to("/home").into_response()),
+    };
+
+    let amount = match form.budget.trim() {
+        "" => None,
+        value => Some(
+            value
+                .replace(",", ".")
+                .parse::<f64>()
+                .map_err(|e| errors::DataError::Internal(format!("Invalid budget {}: {}", value, e)))?,
+        ),
+    };
+    budget::set_budget(&app_state.connection_pool, form.kind, form.name.trim(), amount).await?;
+    Ok(Redirect::to(homepage).into_response())
+}
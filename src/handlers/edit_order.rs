@@ -2,7 +2,7 @@ use askama::Template;
 use umya_spreadsheet::{Spreadsheet};
 use crate::{
     handlers,
-    data::{errors::{self, DataError}, excel, item, order, user}, models::{app::AppState, templates::{CoffeePageTemplate, EditOrderTemplate}}
+    data::{budget, csv_export, errors::{self, DataError}, excel, inventory, item, order, receiving, stock_watch, user}, models::{app::{AppState, BomJob}, budget::{ApproveOrderForm, BudgetKind, BudgetOverrun, BudgetSpend}, distributor_models::DistributorKind, new_order_form_model::CustomItemForm, order_status::OrderTransition, receiving::ItemReceiptForm, stock_watch::StockWatchForm, templates::{CoffeePageTemplate, ApproveOrderTemplate, EditOrderTemplate}}
 };
 use axum::{
     body::{Body, Bytes}, extract::{Multipart, Path, State}, http::{header, HeaderValue, StatusCode}, response::{Html, IntoResponse, Redirect, Response}, Form, Json
@@ -10,7 +10,7 @@ use axum::{
 use tower_sessions::Session;
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use zip::write::FileOptions;
-use std::{collections::{HashMap, HashSet}, io::Write};
+use std::{collections::{hash_map::Entry, HashMap, HashSet}, io::Write};
 use std::io::Cursor;
 
 pub async fn edit_order_handler(
@@ -49,35 +49,39 @@ pub async fn edit_order_handler(
         sub_areas: HashSet::<String>::from_iter(sub_areas).into_iter().collect(),
         proposals: proposals,
         projects: projects,
+        cost_summary: order::get_cost_summary(&app_state.connection_pool, order_id).await?,
+        sourcing: item::get_sourcing_overview(order_id, &app_state.connection_pool).await?,
+        stock_watches: stock_watch::get_watches_for_order(&app_state.connection_pool, order_id).await?,
+        receipts: receiving::get_receipts_for_order(&app_state.connection_pool, order_id).await?,
+        inventory_stock: inventory::get_stock_for_order(&app_state.connection_pool, order_id).await?,
+        custom_items: item::get_custom_items_from_order(order_id, &app_state.connection_pool).await?,
     }.render().unwrap();
     Ok(Html(html_string).into_response())
 }
 
-pub async fn new_order_with_id_handler(
+/// Items and sourcing of an order can only change until it is approved.
+async fn ensure_editable(app_state: &AppState, order_id: i32) -> Result<(), errors::AppError> {
+    let order = order::get_order_from_id(order_id, &app_state.connection_pool).await?;
+    if !order.status().is_editable() {
+        return Err(DataError::Internal(format!("Order {} can no longer be edited ({})", order_id, order.status)).into());
+    }
+    Ok(())
+}
+
+pub async fn submit_order_handler(
     State(app_state): State<AppState>,
-    session: Session,
+    _session: Session,
+    Path(order_id): Path<i32>,
     Form(user_form): Form<HashMap<String, String>>,
-    order_id: i32,
 ) -> Result<Response, errors::AppError> {
-    let order_author_id = session
-    .get::<i32>("authenticated_user_id")
-    .await
-    .map_err(|e| errors::AppError::Session(e))?.unwrap();
+    ensure_editable(&app_state, order_id).await?;
     let description = user_form.get("description").unwrap().trim().to_string();
     let area_division = user_form.get("area_division").unwrap().trim().to_string();
     let area_sub_area = user_form.get("area_sub_area").unwrap().trim().to_string();
-    order::create_order_with_id(
-        &app_state.connection_pool, 
-        order_id, 
-        order_author_id, 
-        description, 
-        area_division, 
-        area_sub_area).await?;
-
     let mut indices: HashSet<i32> = HashSet::new();
     // Collect valid indices based on existing keys
     for key in user_form.keys().map(|s| s.to_string()) {
-        let maybe_index = key.strip_prefix("items_manifacturer_pn_");
+        let maybe_index = key.strip_prefix("items_manufacturer_pn_");
         match maybe_index {
             Some(index_str) => {
                 let index = index_str.parse::<i32>().unwrap();
@@ -88,13 +92,16 @@ pub async fn new_order_with_id_handler(
             }
         }
     }
-    // Now process only the indices that exist
+    // the same part entered twice is a single item with the summed quantity
+    let mut submitted_items: HashMap<(String, String), order::SubmittedItem> = HashMap::new();
     for index in indices {
-        let man_key = format!("items_manifacturer_{}", index);
-        let pn_key = format!("items_manifacturer_pn_{}", index);
+        let man_key = format!("items_manufacturer_{}", index);
+        let pn_key = format!("items_manufacturer_pn_{}", index);
         let quantity_key = format!("items_quantity_{}", index);
         let proposal_key = format!("items_proposal_{}", index);
         let project_key = format!("items_project_{}", index);
+        let distributor_key = format!("items_preferred_distributor_{}", index);
+        let pinned_pn_key = format!("items_pinned_distributor_pn_{}", index);
 
         let manifacturer = user_form.get(&man_key).unwrap_or(&"".to_string()).trim().to_string();
         let manifacturer_pn = user_form.get(&pn_key).unwrap_or(&"".to_string()).trim().to_string();
@@ -106,114 +113,219 @@ pub async fn new_order_with_id_handler(
             .to_string()
             .parse::<i32>()
             .unwrap_or(1);
-        order::add_item_to_order(
-            &app_state.connection_pool,
-            order_id,
-            manifacturer,
-            manifacturer_pn,
+        let preferred_distributor = user_form.get(&distributor_key).and_then(|name| DistributorKind::from_name(name));
+        let pinned_distributor_pn = user_form.get(&pinned_pn_key).map(|pn| pn.trim().to_string());
+
+        let submitted = order::SubmittedItem {
+            manufacturer: manifacturer.clone(),
+            manufacturer_pn: manifacturer_pn.clone(),
             quantity,
             proposal,
             project,
-            None,
-            None,
-        )
-        .await?;
+            preferred_distributor,
+            pinned_distributor_pn,
+        };
+        match submitted_items.entry((manifacturer, manifacturer_pn)) {
+            Entry::Occupied(mut entry) => {
+                // summing the quantities is only right when the rows agree on everything else
+                let existing = entry.get_mut();
+                if existing.proposal != submitted.proposal
+                    || existing.project != submitted.project
+                    || existing.preferred_distributor != submitted.preferred_distributor
+                    || existing.pinned_distributor_pn != submitted.pinned_distributor_pn {
+                    return Err(DataError::Internal(format!(
+                        "{} {} is entered twice with a different proposal, project or distributor",
+                        submitted.manufacturer,
+                        submitted.manufacturer_pn)).into());
+                }
+                existing.quantity += submitted.quantity;
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(submitted);
+            },
+        }
     }
+    let submitted_items: Vec<order::SubmittedItem> = submitted_items.into_values().collect();
 
-    Ok(Redirect::to("/home").into_response())
+    // the order is updated in place, so that what is attached to it (custom items, watches, BOM) is kept
+    order::update_order_details(
+        &app_state.connection_pool,
+        order_id,
+        description,
+        area_division,
+        area_sub_area).await?;
+    order::update_order_items(&app_state.connection_pool, order_id, &submitted_items).await?;
+
+    Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
 }
 
-pub async fn submit_order_handler(
-    State(app_state): State<AppState>,
-    session: Session,
-    Path(order_id): Path<i32>,
-    Form(form): Form<HashMap<String, String>>,
-) -> Result<Response, errors::AppError>{
-    // delete old order
-    order::delete_order(&app_state.connection_pool, order_id).await?;
-    // forward request to new order
-    let response = new_order_with_id_handler(State(app_state), session, Form(form), order_id).await;
-    match response {
-        Ok(_) => {
-            Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
+/// Checks that the logged in user can apply the transition to the order. Returns their id and homepage,
+/// or the redirect to send back when they cannot.
+async fn authorize_transition(
+    app_state: &AppState,
+    session: &Session,
+    order_id: i32,
+    transition: OrderTransition,
+) -> Result<Result<(i32, &'static str), Response>, errors::AppError> {
+    // check user is logged in
+    let user_id = session.get::<i32>("authenticated_user_id")
+    .await
+    .map_err(errors::AppError::Session)?;
+    let id = match user_id {
+        Some(id) => id,
+        None => return Ok(Err(Redirect::to("/").into_response())),
+    };
+    let user_role = user::get_user_role(&app_state.connection_pool, id).await?;
+    let homepage = match user_role.as_str() {
+        "board" => "/board/home",
+        "prof" => "/prof",
+        _ => "/home",
+    };
+    let order = order::get_order_from_id(order_id, &app_state.connection_pool).await?;
+    if !transition.is_allowed(&user_role, order.author_id == id, order.status()) {
+        println!("User {} cannot {} order {} ({})", id, transition.path(), order_id, order.status);
+        return Ok(Err(Redirect::to(homepage).into_response()));
+    }
+    Ok(Ok((id, homepage)))
+}
+
+async fn transition_order(
+    app_state: &AppState,
+    session: &Session,
+    order_id: i32,
+    transition: OrderTransition,
+) -> Result<Response, errors::AppError> {
+    match authorize_transition(app_state, session, order_id, transition).await? {
+        Ok((_, homepage)) => {
+            order::apply_transition(&app_state.connection_pool, order_id, transition).await?;
+            Ok(Redirect::to(homepage).into_response())
         },
-        Err(e) => Err(e)
+        Err(response) => Ok(response),
     }
 }
 
-pub async fn mark_order_ready_handler(State(app_state): State<AppState>, _session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError>{
-    order::mark_order_ready(&app_state.connection_pool, order_id).await?;
-    Ok(Redirect::to("/home").into_response())
+pub async fn submit_order_for_approval_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Submit).await
 }
 
-pub async fn mark_order_unready_handler(State(app_state): State<AppState>,_session: Session,Path(order_id): Path<i32>,) -> Result<Response, errors::AppError>{
-    order::mark_order_unready(&app_state.connection_pool, order_id).await?;
-    Ok(Redirect::to("/home").into_response())
+pub async fn withdraw_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Withdraw).await
 }
 
-pub async fn mark_order_confirmed_handler(
+/// Orders exceeding a proposal or project budget are only approved with a justification, which is recorded.
+/// The professor is notified of every approved order, once the approval is saved.
+pub async fn approve_order_handler(
     State(app_state): State<AppState>,
     session: Session,
     Path(order_id): Path<i32>,
+    Form(form): Form<ApproveOrderForm>,
 ) -> Result<Response, errors::AppError> {
-    // check user is logged in
-    let user_id = session.get::<i32>("authenticated_user_id")
-    .await
-    .map_err(|e| errors::AppError::Session(e))?;
-    match user_id {
-        Some(id) => {
-            // first check if user is authorized to mark as confirmed (i.e. PM or CTO)
-            let user_role_result = user::get_user_role(&app_state.connection_pool, id).await;
-            if let Ok(user_role) = user_role_result {
-                if user_role != "board" {
-                    return Ok(Redirect::to("/home").into_response());
-                }
-                let payload = handlers::prof_homepage::OrderNotificationRequest {
-                    order_id: order_id,
-                    user_id: id,
-                };
-                println!("calling notify prof handler");
-                handlers::prof_homepage::notify_prof_order_confirmed_handler(
-                    State(app_state.clone()),
-                    session.clone(),
-                    Json(payload),
-                ).await?;
-                order::mark_order_confirmed(&app_state.connection_pool, order_id).await?;
-
-                return Ok(Redirect::to("/board/home").into_response());
-            }
-            Ok(Redirect::to("/home").into_response())
-        }
-        None => {
-            Ok(Redirect::to("/").into_response())
-        }
+    let (id, homepage) = match authorize_transition(&app_state, &session, order_id, OrderTransition::Approve).await? {
+        Ok(authorized) => authorized,
+        Err(response) => return Ok(response),
+    };
+    // the spend of the order is known only once all of its items have been sourced
+    if item::has_outdated_sourcing(&app_state.connection_pool, order_id).await? {
+        generate_bom_handler(State(app_state.clone()), session.clone(), Path(order_id)).await?;
+        handlers::prof_homepage::wait_for_bom_job_to_finish(order_id, app_state.clone()).await?;
     }
-}
+    let has_items = !item::get_items_from_order(order_id, &app_state.connection_pool).await?.is_empty();
+    if has_items && item::get_allocations_from_order(order_id, &app_state.connection_pool).await?.is_empty() {
+        return Err(DataError::Internal(format!("Order {} has no sourced items, its spend cannot be checked against the budgets", order_id)).into());
+    }
+    let mut order_spend: Vec<(BudgetKind, Vec<BudgetSpend>)> = Vec::new();
+    let mut overruns: Vec<BudgetOverrun> = Vec::new();
+    for kind in [BudgetKind::Proposal, BudgetKind::Project] {
+        let spend = budget::get_order_spend(&app_state.connection_pool, kind, order_id).await?;
+        overruns.extend(budget::get_budget_overruns(&app_state.connection_pool, kind, &spend).await?);
+        order_spend.push((kind, spend));
+    }
+    let justification = form.override_justification
+        .map(|justification| justification.trim().to_string())
+        .filter(|justification| !justification.is_empty());
+    let justification = match (overruns.is_empty(), justification) {
+        (true, _) => None,
+        (false, None) => {
+            let html_string = ApproveOrderTemplate {
+                order_id,
+                overruns,
+            }.render().unwrap();
+            return Ok(Html(html_string).into_response());
+        },
+        (false, Some(justification)) => Some(justification),
+    };
+
+    // the status, the override and the spend counted against the budgets are saved together
+    let mut transaction = app_state.connection_pool.begin().await.map_err(DataError::Query)?;
+    order::apply_transition(&mut *transaction, order_id, OrderTransition::Approve).await?;
+    if let Some(justification) = &justification {
+        budget::record_budget_override(&mut *transaction, order_id, id, justification, &overruns).await?;
+    }
+    for (kind, spend) in &order_spend {
+        budget::record_order_spend(&mut *transaction, order_id, *kind, spend).await?;
+    }
+    transaction.commit().await.map_err(DataError::Query)?;
 
-pub async fn mark_order_unconfirmed_handler(State(app_state): State<AppState>,session: Session,Path(order_id): Path<i32>,) -> Result<Response, errors::AppError>{
-    // check user is logged in
-    let user_id = session.get::<i32>("authenticated_user_id")
-    .await
-    .map_err(|e| errors::AppError::Session(e))?;
-    match user_id {
-        Some(id) => {
-            // first check if user is authorized to mark as confirmed (i.e. PM or CTO)
-            let user_role_result = user::get_user_role(&app_state.connection_pool, id).await;
-            if let Ok(user_role) = user_role_result {
-                if user_role != "board" {
-                    return Ok(Redirect::to("/home").into_response());
-                } else {
-                    order::mark_order_unconfirmed(&app_state.connection_pool, order_id).await?;
-                    return Ok(Redirect::to("/board/home").into_response());
-                }
-            }
-            Ok(Redirect::to("/home").into_response())
-        }
-        None => {
-            // If user is not logged in, redirect to login page
-            Ok(Redirect::to("/").into_response())
-        }
+    let budget_override = justification.map(|justification| handlers::prof_homepage::BudgetOverrideNotice {
+        justification,
+        overruns: overruns.iter().map(|overrun| overrun.describe()).collect(),
+    });
+    let payload = handlers::prof_homepage::OrderNotificationRequest {
+        order_id,
+        user_id: id,
+        budget_override,
+    };
+    println!("calling notify prof handler");
+    // the order is approved either way: a failed notification is reported on the homepage instead of being retried
+    if let Err(e) = handlers::prof_homepage::notify_prof_order_approved_handler(
+        State(app_state.clone()),
+        session.clone(),
+        Json(payload),
+    ).await {
+        eprintln!("Order {}: approval notification failed: {}", order_id, e);
+        session.insert(
+            "approval_warning",
+            format!("Order {} was approved, but the professor could not be notified: {}", order_id, e),
+        ).await.map_err(errors::AppError::Session)?;
     }
+
+    Ok(Redirect::to(homepage).into_response())
+}
+
+pub async fn unapprove_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Unapprove).await
+}
+
+pub async fn reject_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Reject).await
+}
+
+pub async fn purchase_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Purchase).await
+}
+
+pub async fn ship_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Ship).await
+}
+
+pub async fn receive_order_partially_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::ReceivePartially).await
+}
+
+pub async fn receive_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Receive).await
+}
+
+pub async fn close_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Close).await
+}
+
+pub async fn cancel_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Cancel).await
+}
+
+pub async fn reopen_order_handler(State(app_state): State<AppState>, session: Session, Path(order_id): Path<i32>) -> Result<Response, errors::AppError> {
+    transition_order(&app_state, &session, order_id, OrderTransition::Reopen).await
 }
 
 pub async fn generate_bom_handler(
@@ -221,25 +333,42 @@ pub async fn generate_bom_handler(
     _session: Session,
     Path(order_id): Path<i32>
 ) -> Result<Response, errors::AppError>{
+    ensure_editable(&app_state, order_id).await?;
+    start_bom_job(app_state, order_id, false).await
+}
 
+/// Same as `generate_bom_handler`, but bypasses the distributor cache for this order.
+pub async fn refresh_bom_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>
+) -> Result<Response, errors::AppError>{
+    ensure_editable(&app_state, order_id).await?;
+    start_bom_job(app_state, order_id, true).await
+}
+
+async fn start_bom_job(app_state: AppState, order_id: i32, force_refresh: bool) -> Result<Response, errors::AppError> {
     println!("Starting BOM generation.");
     // spawn tokio task and move to the background
     {
         let mut jobs = app_state.bom_jobs.lock().await;
-        jobs.insert(order_id, "in_progress".to_string());
+        jobs.insert(order_id, BomJob::with_status("in_progress"));
     }
 
     tokio::spawn(async move {
-        let result = order::generate_bom(&app_state.connection_pool, order_id).await;
-        let mut jobs = app_state.bom_jobs.lock().await;
-        jobs.insert(
-            order_id,
-            if result.is_ok() {
-                "done".to_string()
-            } else {
-                "failed".to_string()
+        let result = order::generate_bom(&app_state.connection_pool, &app_state.sourcing, order_id, force_refresh).await;
+        let job = match result {
+            Ok(sourcing_issues) => BomJob {
+                sourcing_issues,
+                ..BomJob::with_status("done")
             },
-        );
+            Err(e) => BomJob {
+                error: Some(e.to_string()),
+                ..BomJob::with_status("failed")
+            },
+        };
+        let mut jobs = app_state.bom_jobs.lock().await;
+        jobs.insert(order_id, job);
     });
     // immediately return the coffee page, waiting for the job to finish
     Ok(Redirect::to(&format!("/orders/{}/coffee", order_id)).into_response())
@@ -250,18 +379,148 @@ pub async fn get_generate_bom_job_status_handler (
     Path(order_id): Path<i32>
 ) -> Result<Response, errors::AppError> {
     let jobs = app_state.bom_jobs.lock().await;
-    let status = jobs
+    let job = jobs
         .get(&order_id)
         .cloned()
-        .unwrap_or_else(|| "not_started".to_string());
+        .unwrap_or_else(|| BomJob::with_status("not_started"));
 
-    let body = serde_json::json!({ "status": status });
+    Ok(Json(job).into_response())
+}
 
-    Ok((
-        StatusCode::OK,
-        [(header::CONTENT_TYPE, "application/json")],
-        body.to_string(),
-    ).into_response())
+/// Landed cost of the distributor assignment chosen at the last BOM generation, compared with the per-item cheapest one.
+pub async fn get_cost_summary_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    match order::get_cost_summary(&app_state.connection_pool, order_id).await? {
+        Some(summary) => Ok(Json(summary).into_response()),
+        None => Ok((StatusCode::NOT_FOUND, "No BOM generated for this order").into_response()),
+    }
+}
+
+pub async fn add_stock_watch_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Path(order_id): Path<i32>,
+    Form(form): Form<StockWatchForm>,
+) -> Result<Response, errors::AppError> {
+    let user_id = session.get::<i32>("authenticated_user_id")
+    .await
+    .map_err(errors::AppError::Session)?;
+    match user_id {
+        Some(id) => {
+            stock_watch::add_stock_watch(&app_state.connection_pool, order_id, form.manufacturer, form.manufacturer_pn, id).await?;
+            Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
+        },
+        None => Ok(Redirect::to("/").into_response()),
+    }
+}
+
+/// Only board members record deliveries, on orders that were purchased and not received in full yet.
+pub async fn add_item_receipt_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Path(order_id): Path<i32>,
+    Form(form): Form<ItemReceiptForm>,
+) -> Result<Response, errors::AppError> {
+    let user_id = session.get::<i32>("authenticated_user_id")
+    .await
+    .map_err(errors::AppError::Session)?;
+    let id = match user_id {
+        Some(id) => id,
+        None => return Ok(Redirect::to("/").into_response()),
+    };
+    if user::get_user_role(&app_state.connection_pool, id).await? != "board" {
+        return Ok(Redirect::to("/home").into_response());
+    }
+    let order = order::get_order_from_id(order_id, &app_state.connection_pool).await?;
+    if !order.status().is_receivable() {
+        return Err(DataError::Internal(format!("Order {} is not waiting for deliveries ({})", order_id, order.status)).into());
+    }
+    let received_on = match form.received_on.trim() {
+        "" => time::OffsetDateTime::now_utc().date(),
+        value => {
+            let format = time::format_description::parse("[year]-[month]-[day]").unwrap();
+            time::Date::parse(value, &format)
+                .map_err(|e| DataError::Internal(format!("Invalid delivery date {}: {}", value, e)))?
+        },
+    };
+    receiving::record_receipt(
+        &app_state.connection_pool,
+        order_id,
+        form.manufacturer,
+        form.manufacturer_pn,
+        form.quantity,
+        received_on,
+        id,
+    ).await?;
+    // the "Delivered" column of the workbooks follows the receipts, the receipt is already saved either way
+    if let Err(e) = order::save_bom_files(&app_state.connection_pool, &app_state.sourcing.pricing, order_id).await {
+        eprintln!("Order {}: failed to refresh the BOM files after a receipt: {}", order_id, e);
+    }
+    Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
+}
+
+pub async fn delete_stock_watch_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Path((order_id, watch_id)): Path<(i32, i32)>,
+) -> Result<Response, errors::AppError> {
+    let user_id = session.get::<i32>("authenticated_user_id")
+    .await
+    .map_err(errors::AppError::Session)?;
+    let id = match user_id {
+        Some(id) => id,
+        None => return Ok(Redirect::to("/").into_response()),
+    };
+    let is_board = user::get_user_role(&app_state.connection_pool, id).await? == "board";
+    stock_watch::delete_stock_watch(&app_state.connection_pool, order_id, watch_id, id, is_board).await?;
+    Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
+}
+
+/// Checks a watch right away, with fresh distributor results.
+pub async fn check_stock_watch_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path((order_id, watch_id)): Path<(i32, i32)>,
+) -> Result<Response, errors::AppError> {
+    let watches = stock_watch::get_watches_for_order(&app_state.connection_pool, order_id).await?;
+    if let Some(watch) = watches.iter().find(|watch| watch.id == watch_id && watch.notified_at.is_none()) {
+        stock_watch::check_watch(&app_state.connection_pool, &app_state.sourcing, watch, true).await?;
+    }
+    Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
+}
+
+pub async fn add_custom_item_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+    Form(form): Form<CustomItemForm>,
+) -> Result<Response, errors::AppError> {
+    ensure_editable(&app_state, order_id).await?;
+    let custom_item = item::NewCustomItem {
+        vendor: form.vendor.trim().to_string(),
+        description: form.description.trim().to_string(),
+        url: form.url.map(|url| url.trim().to_string()).filter(|url| !url.is_empty()),
+        quantity: form.quantity.max(1),
+        unit_price: form.unit_price.max(0.0),
+        shipping: form.shipping.unwrap_or(0.0).max(0.0),
+        proposal: form.proposal,
+        project: form.project,
+    };
+    item::add_custom_item(&app_state.connection_pool, order_id, &custom_item).await?;
+    Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
+}
+
+pub async fn delete_custom_item_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path((order_id, custom_item_id)): Path<(i32, i32)>,
+) -> Result<Response, errors::AppError> {
+    ensure_editable(&app_state, order_id).await?;
+    item::delete_custom_item(&app_state.connection_pool, order_id, custom_item_id).await?;
+    Ok(Redirect::to(&format!("/orders/{}/edit", order_id)).into_response())
 }
 
 pub async fn coffee_page_handler(
@@ -278,8 +537,10 @@ pub async fn download_bom_handler(
     _session: Session,
     Path(order_id): Path<i32>,
 ) -> Result<Response<Body>, errors::AppError> {
+    // the workbooks always reflect the current allocations and sourcing results
+    order::save_bom_files(&app_state.connection_pool, &app_state.sourcing.pricing, order_id).await?;
     let bom_result = sqlx::query!(
-        "SELECT bom_file_mouser, bom_file_digikey, filename FROM order_bom WHERE order_id = $1",
+        "SELECT bom_file_mouser, bom_file_digikey, bom_file_farnell, bom_file_lcsc, bom_file_tme, bom_file_custom, filename FROM order_bom WHERE order_id = $1",
         order_id
     )
     .fetch_optional(&app_state.connection_pool)
@@ -325,6 +586,24 @@ pub async fn download_bom_handler(
                 errors::AppError::Database(errors::DataError::FailedQuery(e.to_string()))
             })?;
 
+            // BOMs generated before Farnell, LCSC and TME support have no workbook for them
+            let optional_boms = [
+                ("farnell", record.bom_file_farnell),
+                ("lcsc", record.bom_file_lcsc),
+                ("tme", record.bom_file_tme),
+                ("custom", record.bom_file_custom), // only when the order has custom items
+            ];
+            for (distributor, bytes) in optional_boms {
+                if let Some(bytes) = bytes {
+                    zip.start_file(format!("{}_{}.xlsx", base_filename, distributor), options).map_err(|e| {
+                        errors::AppError::Database(errors::DataError::FailedQuery(e.to_string()))
+                    })?;
+                    zip.write_all(&bytes).map_err(|e| {
+                        errors::AppError::Database(errors::DataError::FailedQuery(e.to_string()))
+                    })?;
+                }
+            }
+
             zip.finish().map_err(|e| {
                 errors::AppError::Database(errors::DataError::FailedQuery(e.to_string()))
             })?;
@@ -359,37 +638,31 @@ pub async fn delete_order_handler(
     _session: Session,
     Path(order_id): Path<i32>,
 ) -> Result<Response, errors::AppError> {
+    // approved orders are kept for the budgets and the deliveries, they can be cancelled instead
+    let order = order::get_order_from_id(order_id, &app_state.connection_pool).await?;
+    if order.status().is_approved() {
+        return Err(DataError::Internal(format!("Order {} is {} and cannot be deleted", order_id, order.status)).into());
+    }
     order::delete_order(&app_state.connection_pool, order_id).await?;
     println!("Deleted order {}", order_id);
     Ok(Redirect::to("/home").into_response())
 }
 
-pub async fn download_digikey_cart_handler(
-    State(app_state): State<AppState>,
-    _session: Session,
-    Path(order_id): Path<i32>,
-) -> Result<Response, errors::AppError> {
-    // 1. create xlsx file (column 1 quantity, column 2 part number), no header
-    // 1.1 get digikey items from db
-    let items = item::get_items_from_order(order_id, &app_state.connection_pool).await?;
-
+/// Builds an headerless cart file (column 1 quantity, column 2 part number) that can be uploaded to a distributor website.
+fn cart_file_response(rows: Vec<(i32, String)>, filename: String) -> Result<Response, errors::AppError> {
     let mut book: Spreadsheet = umya_spreadsheet::new_file();
 
     let order_sheet = book.get_sheet_mut(&0).unwrap();
     // insert items
-    let mut row = 1;
-    for item in items {
-        if let Some(pn) = item.digikey_pn {
-            order_sheet.get_cell_mut((1, row)).set_value(item.quantity.to_string()); // quantity
-            order_sheet.get_cell_mut((2, row)).set_value(pn); // PN
-            row += 1;
-        }
+    for (row, (quantity, pn)) in (1..).zip(rows) {
+        order_sheet.get_cell_mut((1, row)).set_value(quantity.to_string()); // quantity
+        order_sheet.get_cell_mut((2, row)).set_value(pn); // PN
     }
-    // 2. download file
+    // download file
     let mut buffer = Cursor::new(Vec::new());
     umya_spreadsheet::writer::xlsx::write_writer(&book, &mut buffer).map_err(|e| errors::DataError::Internal(e.to_string()))?;
 
-    let content_disposition = format!(r#"attachment; filename="digikey_cart_{}.xlsx""#, order_id);
+    let content_disposition = format!(r#"attachment; filename="{}""#, filename);
     let response = Response::builder()
             .status(StatusCode::OK)
             .header(header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
@@ -399,46 +672,142 @@ pub async fn download_digikey_cart_handler(
     Ok(response)
 }
 
+pub async fn download_digikey_cart_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    let allocations = item::get_allocations_for_distributor(order_id, DistributorKind::DigiKey, &app_state.connection_pool).await?;
+    let rows = allocations
+        .into_iter()
+        .map(|allocation| (allocation.quantity, allocation.distributor_pn))
+        .collect();
+    cart_file_response(rows, format!("digikey_cart_{}.xlsx", order_id))
+}
+
 pub async fn download_mouser_cart_handler(
     State(app_state): State<AppState>,
     _session: Session,
     Path(order_id): Path<i32>,
 ) -> Result<Response, errors::AppError> {
-    // 1. create xlsx file (column 1 quantity, column 2 part number), no header
-    // 1.1 get mouser items from db
-    let items = item::get_items_from_order(order_id, &app_state.connection_pool).await?;
+    let allocations = item::get_allocations_for_distributor(order_id, DistributorKind::Mouser, &app_state.connection_pool).await?;
+    let rows = allocations
+        .into_iter()
+        .map(|allocation| (allocation.quantity, allocation.distributor_pn))
+        .collect();
+    cart_file_response(rows, format!("mouser_cart_{}.xlsx", order_id))
+}
 
-    let mut book: Spreadsheet = umya_spreadsheet::new_file();
+pub async fn download_farnell_cart_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    let allocations = item::get_allocations_for_distributor(order_id, DistributorKind::Farnell, &app_state.connection_pool).await?;
+    let rows = allocations
+        .into_iter()
+        .map(|allocation| (allocation.quantity, allocation.distributor_pn))
+        .collect();
+    cart_file_response(rows, format!("farnell_cart_{}.xlsx", order_id))
+}
 
-    let order_sheet = book.get_sheet_mut(&0).unwrap();
-    // insert items
-    let mut row = 1;
-    for item in items {
-        if let Some(pn) = item.mouser_pn {
-            order_sheet.get_cell_mut((1, row)).set_value(item.quantity.to_string()); // quantity
-            order_sheet.get_cell_mut((2, row)).set_value(pn); // PN
-            row += 1;
-        }
-    }
-    // 2. download file
-    let mut buffer = Cursor::new(Vec::new());
-    umya_spreadsheet::writer::xlsx::write_writer(&book, &mut buffer).map_err(|e| errors::DataError::Internal(e.to_string()))?;
+pub async fn download_tme_cart_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    let allocations = item::get_allocations_for_distributor(order_id, DistributorKind::Tme, &app_state.connection_pool).await?;
+    let rows = allocations
+        .into_iter()
+        .map(|allocation| (allocation.quantity, allocation.distributor_pn))
+        .collect();
+    cart_file_response(rows, format!("tme_cart_{}.xlsx", order_id))
+}
 
-    let content_disposition = format!(r#"attachment; filename="mouser_cart_{}.xlsx""#, order_id);
+/// LCSC's BOM tool maps columns by header name, so unlike the other carts this one is a headed CSV.
+pub async fn download_lcsc_cart_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    let allocations = item::get_allocations_for_distributor(order_id, DistributorKind::Lcsc, &app_state.connection_pool).await?;
+    let rows: Vec<Vec<String>> = allocations
+        .into_iter()
+        .map(|allocation| vec![allocation.quantity.to_string(), allocation.distributor_pn, allocation.manufacturer_pn])
+        .collect();
+    let csv = csv_export::to_csv(&["Quantity", "LCSC Part Number", "Manufacture Part Number"], &rows);
+    csv_file_response(csv, format!("lcsc_cart_{}.csv", order_id))
+}
+
+fn csv_file_response(csv: String, filename: String) -> Result<Response, errors::AppError> {
+    let content_disposition = format!(r#"attachment; filename="{}""#, filename);
     let response = Response::builder()
             .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+            .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
             .header(header::CONTENT_DISPOSITION, HeaderValue::from_str(&content_disposition).unwrap())
-            .body(Body::from(buffer.into_inner()))
+            .body(Body::from(csv))
             .unwrap();
     Ok(response)
 }
 
+/// Upload file for the Mouser BOM Tool, with the columns of its template. The manufacturer part number lets the tool
+/// match the item when the Mouser part number is not accepted.
+pub async fn download_mouser_bom_csv_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    let export_rows = item::get_bom_export_rows(order_id, DistributorKind::Mouser, &app_state.connection_pool).await?;
+    let rows: Vec<Vec<String>> = export_rows
+        .into_iter()
+        .map(|row| vec![
+            row.distributor_pn,
+            row.manufacturer_pn,
+            row.manufacturer,
+            row.quantity.to_string(),
+            format!("#{} {}", order_id, row.project),
+            row.reference_designators.unwrap_or_default(),
+        ])
+        .collect();
+    let csv = csv_export::to_csv(
+        &["Mouser Part Number", "Manufacturer Part Number", "Manufacturer Name", "Quantity 1", "Customer Part Number", "Reference Designator"],
+        &rows,
+    );
+    csv_file_response(csv, format!("mouser_bom_{}.csv", order_id))
+}
+
+/// Upload file for the Digi-Key BOM Manager, with the columns of its template. As for Mouser, the manufacturer part number
+/// is a fallback for the Digi-Key part number.
+pub async fn download_digikey_bom_csv_handler(
+    State(app_state): State<AppState>,
+    _session: Session,
+    Path(order_id): Path<i32>,
+) -> Result<Response, errors::AppError> {
+    let export_rows = item::get_bom_export_rows(order_id, DistributorKind::DigiKey, &app_state.connection_pool).await?;
+    let rows: Vec<Vec<String>> = export_rows
+        .into_iter()
+        .map(|row| vec![
+            row.quantity.to_string(),
+            row.manufacturer_pn,
+            row.manufacturer,
+            row.distributor_pn,
+            format!("#{} {}", order_id, row.project),
+            row.reference_designators.unwrap_or_default(),
+        ])
+        .collect();
+    let csv = csv_export::to_csv(
+        &["Quantity", "Manufacturer Part Number", "Manufacturer", "Digi-Key Part Number", "Customer Reference", "Reference Designator"],
+        &rows,
+    );
+    csv_file_response(csv, format!("digikey_bom_{}.csv", order_id))
+}
+
 pub async fn bulk_add_handler(
     State(app_state): State<AppState>,
     Path(order_id): Path<i32>,
     mut multipart: Multipart,
 ) -> Result<Response, errors::AppError> {
+    ensure_editable(&app_state, order_id).await?;
     let mut fields: HashMap<String, String> = HashMap::new();
     let mut file_bytes: Option<Bytes> = None;
 
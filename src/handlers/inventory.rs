@@ -0,0 +1,63 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State}, response::{Html, IntoResponse, Redirect, Response}, Form, Json
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use tower_sessions::Session;
+use crate::{
+    data::{errors, inventory},
+    models::{app::AppState, inventory::{InventoryAdjustment, InventoryItem, InventoryQuery}, templates::InventoryTemplate},
+};
+
+pub async fn inventory_page_handler(
+    State(app_state): State<AppState>,
+    Query(query): Query<InventoryQuery>,
+) -> Result<Response, errors::AppError> {
+    let search = query.q.unwrap_or_default();
+    let html_string = InventoryTemplate {
+        items: inventory::search_inventory(&app_state.connection_pool, &search).await?,
+        movements: inventory::get_recent_movements(&app_state.connection_pool, 50).await?,
+        default_location: inventory::default_location(),
+        search,
+    }.render().unwrap();
+    Ok(Html(html_string).into_response())
+}
+
+pub async fn inventory_json_handler(
+    State(app_state): State<AppState>,
+    Query(query): Query<InventoryQuery>,
+) -> Result<Json<Vec<InventoryItem>>, errors::AppError> {
+    let search = query.q.unwrap_or_default();
+    Ok(Json(inventory::search_inventory(&app_state.connection_pool, &search).await?))
+}
+
+async fn apply_adjustment(
+    app_state: &AppState,
+    session: &Session,
+    adjustment: &InventoryAdjustment,
+) -> Result<InventoryItem, errors::AppError> {
+    let user_id = session.get::<i32>("authenticated_user_id")
+    .await
+    .map_err(errors::AppError::Session)?;
+    let mut conn = app_state.connection_pool.acquire().await.map_err(errors::DataError::Query)?;
+    Ok(inventory::adjust_stock(&mut conn, adjustment, None, user_id).await?)
+}
+
+/// Adjusts the inventory from its page, which then shows the adjusted part.
+pub async fn adjust_inventory_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Form(adjustment): Form<InventoryAdjustment>,
+) -> Result<Response, errors::AppError> {
+    let item = apply_adjustment(&app_state, &session, &adjustment).await?;
+    let search = utf8_percent_encode(&item.manufacturer_pn, NON_ALPHANUMERIC).to_string();
+    Ok(Redirect::to(&format!("/inventory?q={}", search)).into_response())
+}
+
+pub async fn adjust_inventory_json_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Json(adjustment): Json<InventoryAdjustment>,
+) -> Result<Json<InventoryItem>, errors::AppError> {
+    Ok(Json(apply_adjustment(&app_state, &session, &adjustment).await?))
+}
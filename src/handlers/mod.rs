@@ -6,4 +6,7 @@ pub mod board_homepage;
 pub mod prof_homepage;
 pub mod order_operations;
 pub mod password_reset;
-pub mod user_settings;
\ No newline at end of file
+pub mod user_settings;
+pub mod price_history;
+pub mod budget;
+pub mod inventory;
@@ -67,7 +67,7 @@ pub async fn submit_order_handler(
     let mut indices: HashSet<i32> = HashSet::new();
     // Collect valid indices based on existing keys
     for key in user_form.keys().map(|s| s.to_string()) {
-        let maybe_index = key.strip_prefix("items_manifacturer_pn_");
+        let maybe_index = key.strip_prefix("items_manufacturer_pn_");
         match maybe_index {
             Some(index_str) => {
                 let index = index_str.parse::<i32>().unwrap();
@@ -80,8 +80,8 @@ pub async fn submit_order_handler(
     }
     // Now process only the indices that exist
     for index in indices {
-        let man_key = format!("items_manifacturer_{}", index);
-        let pn_key = format!("items_manifacturer_pn_{}", index);
+        let man_key = format!("items_manufacturer_{}", index);
+        let pn_key = format!("items_manufacturer_pn_{}", index);
         let quantity_key = format!("items_quantity_{}", index);
         let proposal_key = format!("items_proposal_{}", index);
         let project_key = format!("items_project_{}", index);
@@ -99,13 +99,7 @@ pub async fn submit_order_handler(
         order::add_item_to_order(
             &app_state.connection_pool,
             order_id,
-            manifacturer,
-            manifacturer_pn,
-            quantity,
-            proposal,
-            project,
-            None,
-            None
+            &order::NewOrderItem::new(manifacturer, manifacturer_pn, quantity, proposal, project),
         )
         .await?;
     }
@@ -51,10 +51,13 @@ pub async fn scale_order_handler (
     if session.get::<i32>("authenticated_user_id").await.unwrap_or(None).unwrap_or(-1) != order_author_id {
        return Err(errors::AppError::Database(errors::DataError::FailedQuery("Not authorized.".to_string())));
     }
+    if !order::get_order_from_id(payload.order_id, &app_state.connection_pool).await?.status().is_editable() {
+        return Err(errors::AppError::Database(errors::DataError::Internal(format!("Order {} can no longer be edited", payload.order_id))));
+    }
 
     // scale order, using integer quantities
     let rows_updated = sqlx::query(
-        "UPDATE order_items SET quantity = ROUND(quantity * $1)::int WHERE order_id = $2"
+        "UPDATE order_items SET quantity = ROUND(quantity * $1)::int, sourcing_outdated = TRUE WHERE order_id = $2"
     )
     .bind(payload.scale_factor)
     .bind(payload.order_id)
@@ -101,6 +104,12 @@ pub async fn merge_order_handler (
         return Err(errors::AppError::Database(errors::DataError::FailedQuery("Not authorized.".to_string())));
     }
     println!("authorised");
+    // the target gets new items and the source is deleted, neither can be approved yet
+    for order_id in [payload.source_id, payload.target_id] {
+        if !order::get_order_from_id(order_id, &app_state.connection_pool).await?.status().is_editable() {
+            return Err(errors::AppError::Database(errors::DataError::Internal(format!("Order {} can no longer be edited", order_id))));
+        }
+    }
     // edit target based on merge options
     let options = MergeOrderOption::AddQuantities;
     match options {
@@ -128,13 +137,18 @@ pub async fn merge_order_handler (
             .map(async |item| order::add_item_to_order( // by defaults sums quantities on conflict
                     &app_state.connection_pool, 
                     payload.target_id, 
-                    item.manufacturer.clone(),
-                    item.manufacturer_pn.clone(),
-                    item.quantity.clone(), 
-                    item.proposal.clone(), 
-                    item.project.clone(), 
-                    item.mouser_pn.clone(), 
-                    item.digikey_pn.clone()
+                    &order::NewOrderItem {
+                        manufacturer: item.manufacturer.clone(),
+                        manufacturer_pn: item.manufacturer_pn.clone(),
+                        quantity: item.quantity,
+                        proposal: item.proposal.clone(),
+                        project: item.project.clone(),
+                        mouser_pn: item.mouser_pn.clone(),
+                        digikey_pn: item.digikey_pn.clone(),
+                        farnell_pn: item.farnell_pn.clone(),
+                        lcsc_pn: item.lcsc_pn.clone(),
+                        tme_pn: item.tme_pn.clone(),
+                    }
                 ).await)).await;
         }
     }
@@ -0,0 +1,45 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State}, response::{Html, IntoResponse, Response}, Json
+};
+use crate::{
+    data::{errors, price_history},
+    models::{app::AppState, price_history::{PartQuery, PriceHistory}, templates::PriceHistoryTemplate},
+};
+
+async fn load_price_history(app_state: &AppState, query: PartQuery) -> Result<PriceHistory, errors::AppError> {
+    let manufacturer = query.manufacturer.filter(|m| !m.trim().is_empty());
+    let observations = price_history::get_price_history(
+        &app_state.connection_pool,
+        manufacturer.as_deref(),
+        query.manufacturer_pn.trim(),
+    ).await?;
+    Ok(PriceHistory {
+        manufacturer,
+        manufacturer_pn: query.manufacturer_pn.trim().to_string(),
+        trends: price_history::price_trends(&observations),
+        observations,
+    })
+}
+
+pub async fn price_history_page_handler(
+    State(app_state): State<AppState>,
+    Query(query): Query<PartQuery>,
+) -> Result<Response, errors::AppError> {
+    let history = load_price_history(&app_state, query).await?;
+    let html_string = PriceHistoryTemplate {
+        manufacturer: history.manufacturer.unwrap_or_default(),
+        manufacturer_pn: history.manufacturer_pn,
+        trends: history.trends,
+        // newest first on the page
+        observations: history.observations.into_iter().rev().collect(),
+    }.render().unwrap();
+    Ok(Html(html_string).into_response())
+}
+
+pub async fn price_history_json_handler(
+    State(app_state): State<AppState>,
+    Query(query): Query<PartQuery>,
+) -> Result<Json<PriceHistory>, errors::AppError> {
+    Ok(Json(load_price_history(&app_state, query).await?))
+}
@@ -1,11 +1,10 @@
 use std::{time::Duration, usize};
 use askama::Template;
-use dotenvy::dotenv;
-use lettre::{message::{header, MultiPart, SinglePart}, transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::message::{header, MultiPart, SinglePart};
 use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
 use crate::{
-    data::{errors::{self, AppError, DataError}, order, user}, handlers::{self, edit_order}, models::{app::AppState, templates::ProfHomepageTemplate}
+    data::{budget, errors::{self, AppError, DataError}, mail, order, user}, handlers::{self, edit_order}, models::{app::AppState, templates::ProfHomepageTemplate}
 };
 use axum::{
     extract::{Path, State}, response::{Html, IntoResponse, Redirect, Response}, Json
@@ -29,7 +28,8 @@ pub async fn prof_homepage_handler(
                     return Ok(Redirect::to("/home").into_response());
                 } else {
                     let html_string = ProfHomepageTemplate {
-                        orders: order::get_confirmed_orders(&app_state.connection_pool).await?,
+                        orders: order::get_approved_orders(&app_state.connection_pool).await?,
+                        budgets: budget::get_budget_overview(&app_state.connection_pool).await?,
                     }.render().unwrap();
                     return Ok(Html(html_string).into_response());
                 }
@@ -48,20 +48,24 @@ pub async fn prof_homepage_handler(
 pub struct OrderNotificationRequest {
     pub order_id: i32,
     pub user_id: i32,
+    #[serde(default)]
+    pub budget_override: Option<BudgetOverrideNotice>, // set when the order was approved over budget
     // pub datetime
 
 }
 
-pub async fn notify_prof_order_confirmed_handler(
+#[derive(Serialize, Deserialize)]
+pub struct BudgetOverrideNotice {
+    pub justification: String,
+    pub overruns: Vec<String>,
+}
+
+pub async fn notify_prof_order_approved_handler(
     State(app_state): State<AppState>,
     session: Session,
     Json(payload): Json<OrderNotificationRequest>
 ) -> Result<(), AppError> {
-    // generate bom
-    handlers::edit_order::generate_bom_handler(State(app_state.clone()), session.clone(), Path(payload.order_id)).await?;
-    
-    wait_for_bom_job_to_finish(payload.order_id, app_state.clone()).await?;
-
+    // the order was sourced before its approval, carts and BOMs are built from those results
     // download carts
     let mouser_cart = axum::body::to_bytes(
         handlers::edit_order::download_mouser_cart_handler(
@@ -81,10 +85,58 @@ pub async fn notify_prof_order_confirmed_handler(
             .into_body(), 
         usize::MAX)
         .await.map_err(|e| DataError::Internal(e.to_string()))?;
+    let farnell_cart = axum::body::to_bytes(
+        handlers::edit_order::download_farnell_cart_handler(
+            State(app_state.clone()),
+            session.clone(),
+            Path(payload.order_id))
+            .await?
+            .into_body(), 
+        usize::MAX)
+        .await.map_err(|e| DataError::Internal(e.to_string()))?;
+    let lcsc_cart = axum::body::to_bytes(
+        handlers::edit_order::download_lcsc_cart_handler(
+            State(app_state.clone()),
+            session.clone(),
+            Path(payload.order_id))
+            .await?
+            .into_body(), 
+        usize::MAX)
+        .await.map_err(|e| DataError::Internal(e.to_string()))?;
 
-    // download bom
+    let tme_cart = axum::body::to_bytes(
+        handlers::edit_order::download_tme_cart_handler(
+            State(app_state.clone()),
+            session.clone(),
+            Path(payload.order_id))
+            .await?
+            .into_body(), 
+        usize::MAX)
+        .await.map_err(|e| DataError::Internal(e.to_string()))?;
+
+    // ready-to-checkout Mouser cart, the cart file stays attached in case it cannot be created
+    let mouser_cart_text = match order::create_mouser_cart(&app_state.connection_pool, &app_state.sourcing, payload.order_id).await {
+        Ok(Some(cart_url)) => format!("Carrello Mouser pronto per il checkout: {}\n", cart_url),
+        Ok(None) => "".to_string(),
+        Err(e) => {
+            eprintln!("Order {}: {}", payload.order_id, e);
+            "Non è stato possibile creare il carrello Mouser, usare il file cart_mouser allegato.\n".to_string()
+        },
+    };
+
+    let digikey_list_text = match order::create_digikey_list(&app_state.connection_pool, &app_state.sourcing, payload.order_id).await {
+        Ok(Some(list_url)) => format!("Lista Digi-Key con gli articoli dell'ordine: {}\n", list_url),
+        Ok(None) => "".to_string(),
+        Err(e) => {
+            eprintln!("Order {}: {}", payload.order_id, e);
+            "Non è stato possibile creare la lista Digi-Key, usare il file cart_digikey allegato.\n".to_string()
+        },
+    };
+
+    // download bom, rebuilt from the stored sourcing results
+    order::save_bom_files(&app_state.connection_pool, &app_state.sourcing.pricing, payload.order_id).await?;
     let bom_data = sqlx::query!(
-        "SELECT filename, bom_file_mouser, bom_file_digikey FROM order_bom WHERE order_id = $1",
+        "SELECT filename, bom_file_mouser, bom_file_digikey, bom_file_farnell, bom_file_lcsc, bom_file_tme, bom_file_custom FROM order_bom WHERE order_id = $1",
         payload.order_id
     ).fetch_one(&app_state.connection_pool)
     .await
@@ -111,14 +163,62 @@ pub async fn notify_prof_order_confirmed_handler(
     ).fetch_one(&app_state.connection_pool)
     .await
     .map_err(|e| DataError::FailedQuery(e.to_string()))?;
+    // landed cost of the order, custom vendors included
+    let cost_summary = order::get_cost_summary(&app_state.connection_pool, payload.order_id).await?;
+    let pricing = &app_state.sourcing.pricing;
+    let vat_text = match pricing.vat_exempt {
+        true => "acquisto esente IVA".to_string(),
+        false => format!("IVA {}% esclusa", pricing.vat_rate),
+    };
+    let totals_text = match &cost_summary {
+        Some(summary) => {
+            let custom_vendors_text: String = summary.custom_vendors
+                .iter()
+                .map(|total| format!(
+                    "            - {}: {:.2} {} + {:.2} {} di spedizione\n",
+                    total.vendor,
+                    total.subtotal,
+                    pricing.currency,
+                    total.shipping,
+                    pricing.currency
+                ))
+                .collect();
+            match custom_vendors_text.is_empty() {
+                true => format!("Totale dell'ordine (spedizioni e costi di gestione inclusi, {}): {:.2} {}\n", vat_text, summary.total_cost, pricing.currency),
+                false => format!(
+                    "Totale dell'ordine (spedizioni e costi di gestione inclusi, {}): {:.2} {}\n\n            Articoli da altri fornitori (file \"custom\"):\n{}",
+                    vat_text,
+                    summary.total_cost,
+                    pricing.currency,
+                    custom_vendors_text
+                ),
+            }
+        },
+        None => "".to_string(),
+    };
+    let budget_override_text = match &payload.budget_override {
+        Some(budget_override) => format!(
+            "ATTENZIONE: l'ordine è stato approvato superando il budget di:\n{}\n            Motivazione: {}\n",
+            budget_override.overruns
+                .iter()
+                .map(|overrun| format!("            - {}\n", overrun))
+                .collect::<String>(),
+            budget_override.justification
+        ),
+        None => "".to_string(),
+    };
     let mail_body_text = format!(
         "
             Buongiorno professore,\n\n
-            un ordine è stato confermato da {} (id: {}, mail: {}) in data odierna.\n\n
+            un ordine è stato approvato da {} (id: {}, mail: {}) in data odierna.\n\n
             Ordine #{}: {}\n
             Autore: {} (id: {}, mail: {})\n\n
-            Allegati a questa mail troverà i file di preventivo separati per mouser e digikey (BOM),\n
-            insieme a due altri file (aventi \"cart\" nel nome) che le permetteranno di aggiungere automaticamente gli oggetti al carrello.\n\n
+            {}
+            {}\n
+            {}
+            {}\n
+            Allegati a questa mail troverà i file di preventivo separati per mouser, digikey, farnell, lcsc e tme (BOM),\n
+            insieme ad altri file (aventi \"cart\" nel nome) che le permetteranno di aggiungere automaticamente gli oggetti al carrello.\n\n
             Le auguriamo una buona giornata,\n
             Team PoliTOcean.
         ",
@@ -130,6 +230,10 @@ pub async fn notify_prof_order_confirmed_handler(
         author_data.username,
         order_data.author_id,
         author_data.email.unwrap_or("not found".to_string()),
+        budget_override_text,
+        totals_text,
+        mouser_cart_text,
+        digikey_list_text,
     ); 
 
     let xlsx_ct: header::ContentType = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
@@ -138,6 +242,9 @@ pub async fn notify_prof_order_confirmed_handler(
     let text_ct: header::ContentType = "text/plain; charset=utf-8"
         .parse()
         .map_err(|e: header::ContentTypeErr| DataError::Mail(e.to_string()))?;
+    let csv_ct: header::ContentType = "text/csv; charset=utf-8"
+        .parse()
+        .map_err(|e: header::ContentTypeErr| DataError::Mail(e.to_string()))?;
 
     let mail_body = SinglePart::builder()
             .header(text_ct.clone())
@@ -165,39 +272,54 @@ pub async fn notify_prof_order_confirmed_handler(
         .header(header::ContentDisposition::attachment(&format!("cart_mouser_{}.xlsx", payload.order_id)))
         .body(mouser_cart.to_vec());
     let digikey_cart_att = SinglePart::builder()
-        .header(xlsx_ct)
+        .header(xlsx_ct.clone())
         .header(header::ContentDisposition::attachment(&format!("cart_digikey_{}.xlsx", payload.order_id)))
         .body(digikey_cart.to_vec());
+    let farnell_bom_att = SinglePart::builder()
+        .header(xlsx_ct.clone())
+        .header(header::ContentDisposition::attachment(&format!("{}_farnell_{}.xlsx", bom_data.filename.clone().unwrap_or("name not found".to_string()), payload.order_id)))
+        .body(bom_data.bom_file_farnell.unwrap_or_default());
+    let farnell_cart_att = SinglePart::builder()
+        .header(xlsx_ct.clone())
+        .header(header::ContentDisposition::attachment(&format!("cart_farnell_{}.xlsx", payload.order_id)))
+        .body(farnell_cart.to_vec());
+    let lcsc_bom_att = SinglePart::builder()
+        .header(xlsx_ct.clone())
+        .header(header::ContentDisposition::attachment(&format!("{}_lcsc_{}.xlsx", bom_data.filename.clone().unwrap_or("name not found".to_string()), payload.order_id)))
+        .body(bom_data.bom_file_lcsc.unwrap_or_default());
+    let lcsc_cart_att = SinglePart::builder()
+        .header(csv_ct)
+        .header(header::ContentDisposition::attachment(&format!("cart_lcsc_{}.csv", payload.order_id)))
+        .body(lcsc_cart.to_vec());
+    let tme_bom_att = SinglePart::builder()
+        .header(xlsx_ct.clone())
+        .header(header::ContentDisposition::attachment(&format!("{}_tme_{}.xlsx", bom_data.filename.clone().unwrap_or("name not found".to_string()), payload.order_id)))
+        .body(bom_data.bom_file_tme.unwrap_or_default());
+    let tme_cart_att = SinglePart::builder()
+        .header(xlsx_ct.clone())
+        .header(header::ContentDisposition::attachment(&format!("cart_tme_{}.xlsx", payload.order_id)))
+        .body(tme_cart.to_vec());
 
-    // configure credentials
-    let smtp_server = "smtp.gmail.com";
-    dotenv().ok();
-    let smtp_user = std::env::var("SMTP_USER")
-        .expect("SMTP_USER not set in .env");
-    let smtp_pass = std::env::var("SMTP_PASS")
-        .expect("SMTP_PASS not set in .env");
-
-    let email = Message::builder()
-        .from(smtp_user.parse().map_err(|e: lettre::address::AddressError| DataError::Mail(e.to_string()))?)
-        .to(recipient.parse().map_err(|e: lettre::address::AddressError| DataError::Mail(e.to_string()))?)
-        .subject(&subject)
-        .multipart(
-            MultiPart::mixed()
-                .singlepart(mail_body)
-                .singlepart(mouser_bom_att)
-                .singlepart(digikey_bom_att)
-                .singlepart(mouser_cart_att)
-                .singlepart(digikey_cart_att)
-    ).map_err(|e| DataError::Mail(e.to_string()))?;
-    println!("verifying credentials...");
-    let creds = Credentials::new(smtp_user.to_string(), smtp_pass.to_string());
-    let mailer = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(smtp_server)
-        .map_err(|e| DataError::Mail(e.to_string()))?
-        .credentials(creds)
-        .build();
-
-    mailer.send(email).await.map_err(|e| DataError::Mail(e.to_string()))?;
-    println!("Email sent successfully!");
+    let mut attachments = MultiPart::mixed()
+        .singlepart(mail_body)
+        .singlepart(mouser_bom_att)
+        .singlepart(digikey_bom_att)
+        .singlepart(mouser_cart_att)
+        .singlepart(digikey_cart_att)
+        .singlepart(farnell_bom_att)
+        .singlepart(farnell_cart_att)
+        .singlepart(lcsc_bom_att)
+        .singlepart(lcsc_cart_att)
+        .singlepart(tme_bom_att)
+        .singlepart(tme_cart_att);
+    // items bought outside the distributors, only when the order has some
+    if let Some(custom_bom) = bom_data.bom_file_custom {
+        attachments = attachments.singlepart(SinglePart::builder()
+            .header(xlsx_ct)
+            .header(header::ContentDisposition::attachment(&format!("{}_custom_{}.xlsx", bom_data.filename.clone().unwrap_or("name not found".to_string()), payload.order_id)))
+            .body(custom_bom));
+    }
+    mail::send_mail(&recipient, &subject, attachments).await?;
 
     Ok(())
 }
@@ -205,6 +327,8 @@ pub async fn notify_prof_order_confirmed_handler(
 #[derive(Deserialize)]
 struct JobStatusResponse {
     status: String,
+    #[serde(default)]
+    error: Option<String>,
 }
 
 pub async fn wait_for_bom_job_to_finish(order_id: i32, app_state: AppState) -> Result<(), AppError> {
@@ -234,7 +358,11 @@ pub async fn wait_for_bom_job_to_finish(order_id: i32, app_state: AppState) -> R
 
         if body.status == "failed" {
             println!("❌ Job failed");
-            break;
+            return Err(DataError::Internal(format!(
+                "BOM generation of order {} failed: {}",
+                order_id,
+                body.error.unwrap_or_default()
+            )).into());
         }
 
         sleep(Duration::from_secs(1)).await;
@@ -1,5 +1,5 @@
 use std::net::SocketAddr;
-use politocean_backend::{routes, init, models::app};
+use politocean_backend::{routes, init, models::app, data::{sourcing::SourcingContext, stock_watch}};
 use std::collections::HashMap;
 use tokio::sync::Mutex;
 use std::sync::Arc;
@@ -23,8 +23,12 @@ async fn main() {
             user_id: None,
         },
         bom_jobs: Arc::new(Mutex::new(HashMap::new())),
+        sourcing: Arc::new(SourcingContext::from_env()),
     };
 
+    // re-check the watched items in the background
+    tokio::spawn(stock_watch::run_stock_watches(app_state.connection_pool.clone(), app_state.sourcing.clone()));
+
     println!("Server running on {addr:?}");
 
     let app = routes::get_router(app_state).layer(session_layer);
@@ -1,12 +1,16 @@
 use std::{collections::HashMap, sync::Arc};
 use sqlx::PgPool;
 use tokio::sync::Mutex;
+use serde::Serialize;
+use crate::data::sourcing::SourcingContext;
+use crate::models::distributor_models::SourcingIssue;
 
 #[derive(Clone)]
 pub struct AppState {
     pub connection_pool: PgPool,
     pub current_user: CurrentUser,
-    pub bom_jobs: Arc<Mutex<HashMap<i32, String>>>, // i32 = order_id
+    pub bom_jobs: Arc<Mutex<HashMap<i32, BomJob>>>, // i32 = order_id
+    pub sourcing: Arc<SourcingContext>, // shared HTTP client and distributor request limits
 }
 
 #[derive(Clone, Debug)]
@@ -14,3 +18,21 @@ pub struct CurrentUser {
     pub is_authenticated: bool,
     pub user_id: Option<i32>,
 }
+
+/// State of a background BOM generation, as returned by the job status endpoint.
+#[derive(Clone, Debug, Serialize)]
+pub struct BomJob {
+    pub status: String, // in_progress, done or failed
+    pub error: Option<String>,
+    pub sourcing_issues: Vec<SourcingIssue>,
+}
+
+impl BomJob {
+    pub fn with_status(status: &str) -> BomJob {
+        BomJob {
+            status: status.to_string(),
+            error: None,
+            sourcing_issues: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+/// Budget of a proposal or project and what approved orders have already committed of it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Budget {
+    pub name: String,
+    pub allocated: Option<f64>, // None when no budget has been set
+    pub committed: f64, // spend of the approved orders when they were approved, VAT excluded
+}
+
+impl Budget {
+    pub fn remaining(&self) -> Option<f64> {
+        self.allocated.map(|allocated| allocated - self.committed)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.remaining().is_some_and(|remaining| remaining < 0.0)
+    }
+
+    /// Used share of the budget, in percent.
+    pub fn used_percent(&self) -> Option<f64> {
+        match self.allocated {
+            Some(allocated) if allocated > 0.0 => Some(self.committed / allocated * 100.0),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetOverview {
+    pub proposals: Vec<Budget>,
+    pub projects: Vec<Budget>,
+}
+
+impl BudgetOverview {
+    /// Proposals and projects, in this order, to render both tables with the same markup.
+    pub fn sections(&self) -> Vec<(BudgetKind, &Vec<Budget>)> {
+        vec![(BudgetKind::Proposal, &self.proposals), (BudgetKind::Project, &self.projects)]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetKind {
+    Proposal,
+    Project,
+}
+
+impl BudgetKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BudgetKind::Proposal => "proposal",
+            BudgetKind::Project => "project",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BudgetForm {
+    pub kind: BudgetKind,
+    pub name: String,
+    pub budget: String, // empty to remove the budget
+}
+
+/// A budget that an order would exceed if approved.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetOverrun {
+    pub kind: BudgetKind,
+    pub name: String,
+    pub allocated: f64,
+    pub committed: f64, // by the other approved orders
+    pub order_spend: f64,
+}
+
+impl BudgetOverrun {
+    pub fn excess(&self) -> f64 {
+        self.committed + self.order_spend - self.allocated
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}: budget {:.2}, committed {:.2}, this order {:.2} (over by {:.2})",
+            self.kind.name(),
+            self.name,
+            self.allocated,
+            self.committed,
+            self.order_spend,
+            self.excess()
+        )
+    }
+}
+
+/// Spend of a single order on a proposal or project.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct BudgetSpend {
+    pub name: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApproveOrderForm {
+    pub override_justification: Option<String>,
+}
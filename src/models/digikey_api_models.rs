@@ -102,14 +102,23 @@ pub struct PriceBreak {
     pub unit_price: f64,
     pub total_price: f64,
 }
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct CreateListRequest {
+    pub list_name: String,
+}
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct DigiKeyPart {
-    pub manufacturer: String,
-    pub manufacturer_pn: String,
-    pub description: String,
-    pub digikey_pn: String,
-    pub product_url: String,
-    pub unit_price: f64,
-    pub availability: u32,
-}
\ No newline at end of file
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListPart {
+    pub requested_part_number: String,
+    pub manufacturer_name: String,
+    pub customer_reference: String,
+    pub quantities: Vec<ListPartQuantity>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListPartQuantity {
+    pub quantity: u32,
+}
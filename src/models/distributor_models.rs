@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use crate::models::pricing::PricingSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DistributorKind {
+    Mouser,
+    DigiKey,
+    Farnell,
+    Lcsc,
+    Tme,
+}
+
+impl DistributorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistributorKind::Mouser => "mouser",
+            DistributorKind::DigiKey => "digikey",
+            DistributorKind::Farnell => "farnell",
+            DistributorKind::Lcsc => "lcsc",
+            DistributorKind::Tme => "tme",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DistributorKind> {
+        match name.trim().to_lowercase().as_str() {
+            "mouser" => Some(DistributorKind::Mouser),
+            "digikey" | "digi-key" => Some(DistributorKind::DigiKey),
+            "farnell" | "element14" => Some(DistributorKind::Farnell),
+            "lcsc" => Some(DistributorKind::Lcsc),
+            "tme" => Some(DistributorKind::Tme),
+            _ => None,
+        }
+    }
+
+    /// Base URL of the distributor API, without trailing slash. It can be overridden with
+    /// `<DISTRIBUTOR>_API_URL` (e.g. `MOUSER_API_URL`), for instance to use the mock distributor server.
+    /// LCSC has no public API: its URL is the backend of the website (`/ftps/wm/...` endpoints), which is undocumented
+    /// and may change without notice.
+    pub fn api_base_url(&self) -> String {
+        let default_url = match self {
+            DistributorKind::Mouser => "https://api.mouser.com",
+            DistributorKind::DigiKey => "https://api.digikey.com",
+            DistributorKind::Farnell => "https://api.element14.com",
+            DistributorKind::Lcsc => "https://wmsc.lcsc.com",
+            DistributorKind::Tme => "https://api.tme.eu",
+        };
+        std::env::var(format!("{}_API_URL", self.name().to_uppercase()))
+            .unwrap_or(default_url.to_string())
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotePriceBreak {
+    pub quantity: u32,
+    pub unit_price: f64,
+}
+
+/// A distributor offer for a single manufacturer part, as returned by any supported distributor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistributorQuote {
+    pub distributor: DistributorKind,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub description: String,
+    pub distributor_pn: String,
+    pub product_url: String,
+    pub unit_price: f64, // unit price for the requested quantity
+    pub availability: u32,
+    pub minimum_order_quantity: u32,
+    pub price_breaks: Vec<QuotePriceBreak>,
+}
+
+impl DistributorQuote {
+    pub fn unit_price_for(&self, quantity: u32) -> Option<f64> {
+        let mut unit_price = None;
+        for price_break in &self.price_breaks {
+            if quantity >= price_break.quantity {
+                unit_price = Some(price_break.unit_price);
+            }
+        }
+        unit_price
+    }
+
+    /// Best way to buy at least `requested` pieces: the quantity is raised to the minimum order quantity
+    /// and then to the next price breaks, as long as the extended price drops and stock covers it.
+    /// Returns `None` when the distributor cannot supply the part.
+    pub fn offer_for(&self, requested: u32) -> Option<Offer> {
+        let minimum = requested.max(self.minimum_order_quantity);
+        if self.availability < minimum {
+            return None;
+        }
+        let candidates = std::iter::once(minimum).chain(
+            self.price_breaks
+                .iter()
+                .map(|price_break| price_break.quantity)
+                .filter(|&quantity| quantity > minimum && quantity <= self.availability),
+        );
+
+        let mut best: Option<(u32, f64)> = None;
+        for quantity in candidates {
+            let unit_price = match self.unit_price_for(quantity) {
+                Some(unit_price) if unit_price > 0.0 => unit_price,
+                _ => continue,
+            };
+            let extended_price = quantity as f64 * unit_price;
+            // on (almost) equal extended price keep the smaller quantity
+            if best.is_none_or(|(q, p)| extended_price + 1e-9 < q as f64 * p) {
+                best = Some((quantity, unit_price));
+            }
+        }
+
+        best.map(|(quantity, unit_price)| Offer {
+            quote: self.clone(),
+            quantity,
+            unit_price,
+        })
+    }
+}
+
+/// A quote together with the quantity that should actually be bought from it.
+#[derive(Debug, Clone)]
+pub struct Offer {
+    pub quote: DistributorQuote,
+    pub quantity: u32,
+    pub unit_price: f64,
+}
+
+impl Offer {
+    pub fn extended_price(&self) -> f64 {
+        self.quantity as f64 * self.unit_price
+    }
+
+    /// Explains why the quantity differs from the requested one, empty when it does not.
+    pub fn adjustment_note(&self, requested: u32) -> String {
+        if self.quantity <= requested {
+            return "".to_string();
+        }
+        if requested < self.quote.minimum_order_quantity && self.quantity == self.quote.minimum_order_quantity {
+            return format!("Raised from {} to the minimum order quantity ({})", requested, self.quantity);
+        }
+        // without the raise at least the minimum order quantity would have been bought
+        let baseline_quantity = requested.max(self.quote.minimum_order_quantity);
+        match self.quote.unit_price_for(baseline_quantity) {
+            Some(baseline_unit_price) => format!(
+                "Raised from {} to {} for a cheaper price break (saves {:.2})",
+                requested,
+                self.quantity,
+                baseline_quantity as f64 * baseline_unit_price - self.extended_price()
+            ),
+            None => format!("Raised from {} to {} to reach the first price break", requested, self.quantity),
+        }
+    }
+}
+
+/// A distributor search that did not produce a quote for an item, as reported by BOM generation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourcingIssue {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub distributor: DistributorKind,
+    pub error: String, // kind of error, see `SourcingError::kind`
+    pub message: String,
+    pub retryable: bool,
+}
+
+/// Shipping charged by a distributor for a single basket: free once the basket reaches the threshold.
+/// The handling fee is charged on every basket, free shipping or not.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ShippingRule {
+    pub cost: f64,
+    pub free_threshold: Option<f64>,
+    pub handling: f64,
+}
+
+impl ShippingRule {
+    /// Reads `<DISTRIBUTOR>_SHIPPING_COST` and `<DISTRIBUTOR>_FREE_SHIPPING_THRESHOLD` (e.g. `MOUSER_SHIPPING_COST`),
+    /// falling back to the distributor's usual rates. A threshold of 0 or less means shipping is never free.
+    /// The handling fee comes from the pricing settings.
+    pub fn from_env(kind: DistributorKind, pricing: &PricingSettings) -> ShippingRule {
+        let (default_cost, default_threshold) = match kind {
+            DistributorKind::Mouser => (20.0, Some(50.0)),
+            DistributorKind::DigiKey => (18.0, Some(50.0)),
+            DistributorKind::Farnell => (15.0, Some(35.0)),
+            DistributorKind::Lcsc => (20.0, None),
+            DistributorKind::Tme => (12.0, Some(200.0)),
+        };
+        let prefix = kind.name().to_uppercase();
+        let cost = std::env::var(format!("{}_SHIPPING_COST", prefix))
+            .ok()
+            .and_then(|value| value.parse::<f64>().ok())
+            .unwrap_or(default_cost);
+        let free_threshold = match std::env::var(format!("{}_FREE_SHIPPING_THRESHOLD", prefix))
+            .ok()
+            .and_then(|value| value.parse::<f64>().ok())
+        {
+            Some(threshold) if threshold > 0.0 => Some(threshold),
+            Some(_) => None,
+            None => default_threshold,
+        };
+        ShippingRule { cost, free_threshold, handling: pricing.handling_fee(kind) }
+    }
+
+    pub fn cost_for(&self, subtotal: f64) -> f64 {
+        match self.free_threshold {
+            _ if subtotal <= 0.0 => 0.0,
+            Some(threshold) if subtotal >= threshold => 0.0,
+            _ => self.cost,
+        }
+    }
+
+    pub fn handling_for(&self, subtotal: f64) -> f64 {
+        match subtotal > 0.0 {
+            true => self.handling,
+            false => 0.0,
+        }
+    }
+}
+
+/// What an order costs at a single distributor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistributorTotal {
+    pub distributor: DistributorKind,
+    pub subtotal: f64,
+    pub shipping: f64,
+    #[serde(default)]
+    pub handling: f64,
+}
+
+/// Where an item ended up after the order-level optimisation, compared with the per-item cheapest choice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemAssignment {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub distributors: Vec<DistributorKind>,
+    pub greedy_distributors: Vec<DistributorKind>,
+    pub cost: f64,
+}
+
+/// Landed cost (items plus shipping) of the chosen assignment and of the per-item cheapest one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostSummary {
+    pub distributors: Vec<DistributorTotal>,
+    pub items: Vec<ItemAssignment>,
+    pub total_cost: f64,
+    pub greedy_total_cost: f64,
+    pub saving: f64,
+    #[serde(default)]
+    pub custom_vendors: Vec<CustomVendorTotal>, // not part of the optimisation, added when the summary is read
+}
+
+/// Cost of the custom items bought from a vendor outside the distributor APIs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomVendorTotal {
+    pub vendor: String,
+    pub subtotal: f64,
+    pub shipping: f64,
+}
+
+impl CostSummary {
+    /// Adds the custom vendors to the totals; the saving is unaffected as they are bought in any case.
+    pub fn with_custom_vendors(mut self, custom_vendors: Vec<CustomVendorTotal>) -> CostSummary {
+        let custom_cost: f64 = custom_vendors.iter().map(|total| total.subtotal + total.shipping).sum();
+        self.total_cost += custom_cost;
+        self.greedy_total_cost += custom_cost;
+        self.custom_vendors = custom_vendors;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(minimum_order_quantity: u32, price_breaks: &[(u32, f64)]) -> DistributorQuote {
+        DistributorQuote {
+            distributor: DistributorKind::Mouser,
+            manufacturer: "Texas Instruments".to_string(),
+            manufacturer_pn: "LM358".to_string(),
+            description: "".to_string(),
+            distributor_pn: "595-LM358".to_string(),
+            product_url: "".to_string(),
+            unit_price: 0.0,
+            availability: 1000,
+            minimum_order_quantity,
+            price_breaks: price_breaks
+                .iter()
+                .map(|(quantity, unit_price)| QuotePriceBreak { quantity: *quantity, unit_price: *unit_price })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn adjustment_note_saving_is_measured_from_the_minimum_order_quantity() {
+        // 5 requested, at least 10 must be bought for 10.00, 20 cost 8.00
+        let offer = Offer {
+            quote: quote(10, &[(10, 1.0), (20, 0.4)]),
+            quantity: 20,
+            unit_price: 0.4,
+        };
+        assert_eq!(offer.adjustment_note(5), "Raised from 5 to 20 for a cheaper price break (saves 2.00)");
+    }
+
+    #[test]
+    fn adjustment_note_for_the_minimum_order_quantity() {
+        let offer = Offer {
+            quote: quote(10, &[(10, 1.0)]),
+            quantity: 10,
+            unit_price: 1.0,
+        };
+        assert_eq!(offer.adjustment_note(5), "Raised from 5 to the minimum order quantity (10)");
+    }
+}
@@ -0,0 +1,40 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FarnellResponse {
+    pub manufacturer_part_number_search_return: Option<SearchReturn>,
+    pub premier_farnell_part_number_return: Option<SearchReturn>, // answer to an `id:` search
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchReturn {
+    pub number_of_results: u32,
+    pub products: Option<Vec<Product>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Product {
+    pub sku: String,
+    pub display_name: Option<String>,
+    pub brand_name: Option<String>,
+    pub vendor_name: Option<String>,
+    pub translated_manufacturer_part_number: Option<String>,
+    pub translated_minimum_order_quality: Option<u32>, // sic, as spelled by the element14 API
+    pub prices: Option<Vec<PriceBreak>>,
+    pub stock: Option<Stock>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PriceBreak {
+    pub from: u32,
+    pub to: u32,
+    pub cost: f64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Stock {
+    pub level: u32,
+}
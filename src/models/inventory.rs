@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Quantity of a part stored in one place of the lab.
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct InventoryItem {
+    pub id: i32,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub location: String,
+    pub quantity: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl InventoryItem {
+    pub fn get_date(&self) -> String {
+        self.updated_at.format("%d/%m/%Y %H:%M").to_string()
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct InventoryMovement {
+    pub id: i32,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub location: String,
+    pub change: i32,
+    pub reason: String,
+    pub order_id: Option<i32>,
+    pub username: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl InventoryMovement {
+    pub fn get_date(&self) -> String {
+        self.created_at.format("%d/%m/%Y %H:%M").to_string()
+    }
+}
+
+/// What the lab already has of an order item, over all locations.
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct InventoryStock {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub required: i32,
+    pub on_hand: i32,
+    pub locations: String, // e.g. "Drawer A3 (10), Incoming (5)"
+}
+
+#[derive(Deserialize, Debug)]
+pub struct InventoryQuery {
+    pub q: Option<String>, // searched in manufacturer, part number and location
+}
+
+/// A change of quantity, positive when parts are added and negative when they are taken.
+#[derive(Deserialize, Debug)]
+pub struct InventoryAdjustment {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub location: String, // the default location when empty
+    pub change: i32,
+    pub reason: String,
+}
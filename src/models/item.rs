@@ -1,3 +1,6 @@
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use crate::models::distributor_models::{DistributorKind, DistributorQuote, QuotePriceBreak};
+
 #[derive(sqlx::FromRow, Debug, Clone)]
 pub struct OrderItem {
     pub order_id: i32,
@@ -8,4 +11,151 @@ pub struct OrderItem {
     pub project: String,
     pub mouser_pn: Option<String>,
     pub digikey_pn: Option<String>,
-}
\ No newline at end of file
+    pub farnell_pn: Option<String>,
+    pub lcsc_pn: Option<String>,
+    pub tme_pn: Option<String>,
+    pub purchase_quantity: Option<i32>, // total quantity bought across distributors, set by BOM generation
+    pub sourcing_notes: Option<String>,
+    pub preferred_distributor: Option<String>, // distributor name, the item is only searched there
+    pub pinned_distributor_pn: Option<String>, // exact part number to buy from the preferred distributor
+    pub reference_designators: Option<String>, // from the KiCad BOM, e.g. "R1, R2, R5"
+    pub received_quantity: i32, // sum of the item receipts
+    pub sourcing_outdated: bool, // added or changed since the last BOM generation
+}
+
+impl OrderItem {
+    /// Quantity expected to be delivered: what was bought, or what was requested before BOM generation.
+    pub fn expected_quantity(&self) -> i32 {
+        self.purchase_quantity.unwrap_or(self.quantity)
+    }
+
+    pub fn is_received(&self) -> bool {
+        self.received_quantity >= self.expected_quantity()
+    }
+
+    /// Distributor the item is pinned to, if any.
+    pub fn pinned_distributor(&self) -> Option<DistributorKind> {
+        self.preferred_distributor.as_deref().and_then(DistributorKind::from_name)
+    }
+
+    /// Part number to search for: the pinned distributor part number when there is one.
+    pub fn search_pn(&self) -> &str {
+        match (self.pinned_distributor(), self.pinned_distributor_pn.as_deref()) {
+            (Some(_), Some(pinned_pn)) if !pinned_pn.trim().is_empty() => pinned_pn.trim(),
+            _ => &self.manufacturer_pn,
+        }
+    }
+}
+
+/// The share of an order item bought from a single distributor.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct ItemAllocation {
+    pub order_id: i32,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub distributor: String,
+    pub distributor_pn: String,
+    pub quantity: i32,
+    pub unit_price: f64,
+}
+
+/// A row of the distributor BOM tool exports: an allocation together with the item it belongs to.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct BomExportRow {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub distributor_pn: String,
+    pub quantity: i32,
+    pub project: String,
+    pub reference_designators: Option<String>,
+}
+
+/// An item bought outside the distributor APIs, with a manually entered price.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct CustomOrderItem {
+    pub id: i32,
+    pub order_id: i32,
+    pub vendor: String,
+    pub description: String,
+    pub url: Option<String>,
+    pub quantity: i32,
+    pub unit_price: f64,
+    pub shipping: f64,
+    pub proposal: String,
+    pub project: String,
+}
+
+impl CustomOrderItem {
+    pub fn extended_price(&self) -> f64 {
+        self.quantity as f64 * self.unit_price
+    }
+}
+
+/// What a distributor answered for an item at the last BOM generation: either a quote or an error.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct ItemSourcingResult {
+    pub order_id: i32,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub distributor: String,
+    pub distributor_pn: Option<String>,
+    pub quoted_manufacturer: Option<String>,
+    pub quoted_manufacturer_pn: Option<String>,
+    pub description: Option<String>,
+    pub product_url: Option<String>,
+    pub unit_price: Option<f64>,
+    pub availability: Option<i32>,
+    pub minimum_order_quantity: Option<i32>,
+    pub price_breaks: Option<String>,
+    pub error: Option<String>,
+    pub error_message: Option<String>,
+}
+
+impl ItemSourcingResult {
+    /// The stored quote, `None` for results recording an error.
+    pub fn to_quote(&self) -> Option<DistributorQuote> {
+        Some(DistributorQuote {
+            distributor: DistributorKind::from_name(&self.distributor)?,
+            manufacturer: self.quoted_manufacturer.clone().unwrap_or_default(),
+            manufacturer_pn: self.quoted_manufacturer_pn.clone().unwrap_or_default(),
+            description: self.description.clone().unwrap_or_default(),
+            distributor_pn: self.distributor_pn.clone()?,
+            product_url: self.product_url.clone().unwrap_or_default(),
+            unit_price: self.unit_price.unwrap_or_default(),
+            availability: self.availability.unwrap_or_default().max(0) as u32,
+            minimum_order_quantity: self.minimum_order_quantity.unwrap_or(1).max(1) as u32,
+            price_breaks: self.price_breaks
+                .as_deref()
+                .and_then(|json| serde_json::from_str::<Vec<QuotePriceBreak>>(json).ok())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// One line of the sourcing table on the order page: a distributor share of an item,
+/// or the item alone when it could not be sourced.
+#[derive(Debug, Clone)]
+pub struct SourcingOverviewRow {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub requested_quantity: i32,
+    pub distributor: Option<String>,
+    pub distributor_pn: Option<String>,
+    pub quantity: i32,
+    pub unit_price: f64,
+    pub extended_price: f64,
+    pub availability: Option<i32>,
+    pub product_url: Option<String>,
+    pub notes: String,
+}
+
+impl SourcingOverviewRow {
+    /// Link to the price history page of the item.
+    pub fn history_url(&self) -> String {
+        format!(
+            "/parts/history?manufacturer={}&manufacturer_pn={}",
+            utf8_percent_encode(&self.manufacturer, NON_ALPHANUMERIC),
+            utf8_percent_encode(&self.manufacturer_pn, NON_ALPHANUMERIC)
+        )
+    }
+}
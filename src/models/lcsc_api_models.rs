@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct LcscSearchResponse {
+    pub code: i32,
+    pub result: Option<SearchResult>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    #[serde(rename = "productSearchResultVO")]
+    pub product_search_result: Option<ProductSearchResult>,
+    #[serde(rename = "tipProductDetailUrlVO")]
+    pub tip_product_detail: Option<TipProductDetail>, // set instead of the list on an exact match
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProductSearchResult {
+    pub total_count: u32,
+    pub product_list: Option<Vec<Product>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TipProductDetail {
+    pub product_code: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LcscDetailResponse {
+    pub code: i32,
+    pub result: Option<Product>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Product {
+    pub product_code: String,
+    pub product_model: Option<String>,
+    pub brand_name_en: Option<String>,
+    pub product_intro_en: Option<String>,
+    pub stock_number: Option<u32>,
+    pub min_buy_number: Option<u32>,
+    pub product_price_list: Option<Vec<PriceBreak>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceBreak {
+    pub ladder: u32,
+    pub currency_price: Option<f64>,
+    pub usd_price: Option<f64>,
+}
@@ -4,7 +4,18 @@ pub mod new_order_form_model;
 pub mod app;
 pub mod digikey_api_models;
 pub mod mouser_api_models;
+pub mod farnell_api_models;
+pub mod lcsc_api_models;
+pub mod tme_api_models;
 pub mod item;
 pub mod order_operations;
 pub mod password_reset;
-pub mod user_info;
\ No newline at end of file
+pub mod user_info;
+pub mod distributor_models;
+pub mod price_history;
+pub mod stock_watch;
+pub mod pricing;
+pub mod budget;
+pub mod order_status;
+pub mod receiving;
+pub mod inventory;
@@ -19,9 +19,16 @@ pub struct InnerRequest {
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct MouserResponse {
+    pub errors: Option<Vec<MouserError>>,
     pub search_results: Option<SearchResults>,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct MouserError {
+    pub message: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct SearchResults {
@@ -38,17 +45,7 @@ pub struct Part {
     pub product_detail_url: Option<String>,
     pub price_breaks: Option<Vec<PriceBreak>>,
     pub availability: Option<String>,
-}
-
-#[derive(Deserialize, Debug, Clone)]
-pub struct MouserPart {
-    pub manufacturer: String,
-    pub manufacturer_pn: String,
-    pub description: String,
-    pub mouser_pn: String,
-    pub product_url: String,
-    pub unit_price: f64,
-    pub availability: u32,
+    pub min: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -57,4 +54,33 @@ pub struct PriceBreak {
     pub Quantity: u32,
     pub Price: String,
     pub Currency: String,
-}
\ No newline at end of file
+}
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CartInsertRequest {
+    pub cart_key: String, // empty to create a new cart
+    pub cart_items: Vec<CartItem>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CartItem {
+    pub mouser_part_number: String,
+    pub quantity: u32,
+    pub customer_part_number: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct CartResponse {
+    pub errors: Option<Vec<MouserError>>,
+    pub cart_key: Option<String>,
+    pub cart_items: Option<Vec<CartResponseItem>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct CartResponseItem {
+    pub mouser_part_number: Option<String>,
+    pub errors: Option<Vec<MouserError>>,
+}
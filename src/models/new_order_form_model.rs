@@ -8,4 +8,16 @@ pub struct NewOrderFormModel {
 
     #[serde(flatten)]
     pub products: HashMap<String, String>,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CustomItemForm {
+    pub vendor: String,
+    pub description: String,
+    pub url: Option<String>,
+    pub quantity: i32,
+    pub unit_price: f64,
+    pub shipping: Option<f64>,
+    pub proposal: String,
+    pub project: String,
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+
+/// Where an order is in its lifecycle, stored by name in `orders.status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Draft,
+    Submitted,
+    Approved,
+    Purchased,
+    Shipped,
+    PartiallyReceived,
+    Received,
+    Closed,
+    Rejected,
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrderStatus::Draft => "draft",
+            OrderStatus::Submitted => "submitted",
+            OrderStatus::Approved => "approved",
+            OrderStatus::Purchased => "purchased",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::PartiallyReceived => "partially_received",
+            OrderStatus::Received => "received",
+            OrderStatus::Closed => "closed",
+            OrderStatus::Rejected => "rejected",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OrderStatus> {
+        match name {
+            "draft" => Some(OrderStatus::Draft),
+            "submitted" => Some(OrderStatus::Submitted),
+            "approved" => Some(OrderStatus::Approved),
+            "purchased" => Some(OrderStatus::Purchased),
+            "shipped" => Some(OrderStatus::Shipped),
+            "partially_received" => Some(OrderStatus::PartiallyReceived),
+            "received" => Some(OrderStatus::Received),
+            "closed" => Some(OrderStatus::Closed),
+            "rejected" => Some(OrderStatus::Rejected),
+            "cancelled" => Some(OrderStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OrderStatus::Draft => "To be completed ...",
+            OrderStatus::Submitted => "Waiting for approval ...",
+            OrderStatus::Approved => "Approved ✅",
+            OrderStatus::Purchased => "Purchased 🛒",
+            OrderStatus::Shipped => "Shipped 🚚",
+            OrderStatus::PartiallyReceived => "Partially received 📦",
+            OrderStatus::Received => "Received 📦",
+            OrderStatus::Closed => "All done! ✅",
+            OrderStatus::Rejected => "Rejected ❌",
+            OrderStatus::Cancelled => "Cancelled",
+        }
+    }
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            OrderStatus::Draft | OrderStatus::Rejected => " #E85F5C",
+            OrderStatus::Submitted => " #FFC107",
+            OrderStatus::Cancelled => " #C0C0C0",
+            _ => " #ACF39D",
+        }
+    }
+
+    /// Statuses of the orders approved by the board, whose spend counts against the budgets.
+    pub fn approved_statuses() -> Vec<OrderStatus> {
+        vec![
+            OrderStatus::Approved,
+            OrderStatus::Purchased,
+            OrderStatus::Shipped,
+            OrderStatus::PartiallyReceived,
+            OrderStatus::Received,
+            OrderStatus::Closed,
+        ]
+    }
+
+    /// Statuses after which nothing is bought for the order anymore.
+    pub fn finished_statuses() -> Vec<OrderStatus> {
+        vec![
+            OrderStatus::Received,
+            OrderStatus::Closed,
+            OrderStatus::Rejected,
+            OrderStatus::Cancelled,
+        ]
+    }
+
+    pub fn is_approved(&self) -> bool {
+        OrderStatus::approved_statuses().contains(self)
+    }
+
+    /// Deliveries are recorded from the purchase until every item has been received.
+    pub fn is_receivable(&self) -> bool {
+        matches!(self, OrderStatus::Purchased | OrderStatus::Shipped | OrderStatus::PartiallyReceived)
+    }
+
+    /// Items can only be changed before the board approves the order.
+    pub fn is_editable(&self) -> bool {
+        matches!(self, OrderStatus::Draft | OrderStatus::Submitted)
+    }
+}
+
+/// A change of status requested by a user. Each transition is only allowed from some statuses and for some roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderTransition {
+    Submit,
+    Withdraw,
+    Approve,
+    Unapprove,
+    Reject,
+    Purchase,
+    Ship,
+    ReceivePartially,
+    Receive,
+    Close,
+    Cancel,
+    Reopen,
+}
+
+impl OrderTransition {
+    pub fn from_statuses(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderTransition::Submit => &[OrderStatus::Draft],
+            OrderTransition::Withdraw => &[OrderStatus::Submitted],
+            OrderTransition::Approve => &[OrderStatus::Submitted],
+            OrderTransition::Unapprove => &[OrderStatus::Approved],
+            OrderTransition::Reject => &[OrderStatus::Submitted],
+            OrderTransition::Purchase => &[OrderStatus::Approved],
+            OrderTransition::Ship => &[OrderStatus::Purchased],
+            OrderTransition::ReceivePartially => &[OrderStatus::Purchased, OrderStatus::Shipped],
+            OrderTransition::Receive => &[OrderStatus::Purchased, OrderStatus::Shipped, OrderStatus::PartiallyReceived],
+            OrderTransition::Close => &[OrderStatus::Received],
+            OrderTransition::Cancel => &[OrderStatus::Draft, OrderStatus::Submitted, OrderStatus::Approved],
+            OrderTransition::Reopen => &[OrderStatus::Rejected, OrderStatus::Cancelled],
+        }
+    }
+
+    pub fn target(&self) -> OrderStatus {
+        match self {
+            OrderTransition::Submit => OrderStatus::Submitted,
+            OrderTransition::Withdraw => OrderStatus::Draft,
+            OrderTransition::Approve => OrderStatus::Approved,
+            OrderTransition::Unapprove => OrderStatus::Submitted,
+            OrderTransition::Reject => OrderStatus::Rejected,
+            OrderTransition::Purchase => OrderStatus::Purchased,
+            OrderTransition::Ship => OrderStatus::Shipped,
+            OrderTransition::ReceivePartially => OrderStatus::PartiallyReceived,
+            OrderTransition::Receive => OrderStatus::Received,
+            OrderTransition::Close => OrderStatus::Closed,
+            OrderTransition::Cancel => OrderStatus::Cancelled,
+            OrderTransition::Reopen => OrderStatus::Draft,
+        }
+    }
+
+    /// Whether a user with `role` may apply the transition to an order in `status`; `is_author` tells if they created it.
+    /// The board can apply every transition, the professor follows the purchase and advisors manage their own drafts.
+    pub fn is_allowed(&self, role: &str, is_author: bool, status: OrderStatus) -> bool {
+        if !self.from_statuses().contains(&status) {
+            return false;
+        }
+        match role {
+            "board" => true,
+            "prof" => matches!(
+                self,
+                OrderTransition::Reject
+                    | OrderTransition::Purchase
+                    | OrderTransition::Ship
+                    | OrderTransition::ReceivePartially
+                    | OrderTransition::Receive
+                    | OrderTransition::Close
+            ),
+            _ => is_author && match self {
+                OrderTransition::Submit | OrderTransition::Withdraw | OrderTransition::Reopen => true,
+                // once approved only the board can cancel the order
+                OrderTransition::Cancel => status != OrderStatus::Approved,
+                _ => false,
+            },
+        }
+    }
+
+    pub fn all() -> Vec<OrderTransition> {
+        vec![
+            OrderTransition::Submit,
+            OrderTransition::Withdraw,
+            OrderTransition::Approve,
+            OrderTransition::Unapprove,
+            OrderTransition::Reject,
+            OrderTransition::Purchase,
+            OrderTransition::Ship,
+            OrderTransition::ReceivePartially,
+            OrderTransition::Receive,
+            OrderTransition::Close,
+            OrderTransition::Cancel,
+            OrderTransition::Reopen,
+        ]
+    }
+
+    /// Last segment of the `/orders/:id/...` route applying the transition.
+    pub fn path(&self) -> &'static str {
+        match self {
+            OrderTransition::Submit => "submit",
+            OrderTransition::Withdraw => "withdraw",
+            OrderTransition::Approve => "approve",
+            OrderTransition::Unapprove => "unapprove",
+            OrderTransition::Reject => "reject",
+            OrderTransition::Purchase => "purchase",
+            OrderTransition::Ship => "ship",
+            OrderTransition::ReceivePartially => "receive-partially",
+            OrderTransition::Receive => "receive",
+            OrderTransition::Close => "close",
+            OrderTransition::Cancel => "cancel",
+            OrderTransition::Reopen => "reopen",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OrderTransition::Submit => "Submit for approval",
+            OrderTransition::Withdraw => "Withdraw",
+            OrderTransition::Approve => "Approve",
+            OrderTransition::Unapprove => "Revoke approval",
+            OrderTransition::Reject => "Reject",
+            OrderTransition::Purchase => "Mark as purchased",
+            OrderTransition::Ship => "Mark as shipped",
+            OrderTransition::ReceivePartially => "Mark as partially received",
+            OrderTransition::Receive => "Mark as received",
+            OrderTransition::Close => "Close",
+            OrderTransition::Cancel => "Cancel",
+            OrderTransition::Reopen => "Reopen",
+        }
+    }
+
+    /// Labels for the professor homepage, which is in Italian.
+    pub fn italian_label(&self) -> &'static str {
+        match self {
+            OrderTransition::Submit => "Invia per approvazione",
+            OrderTransition::Withdraw => "Ritira",
+            OrderTransition::Approve => "Approva",
+            OrderTransition::Unapprove => "Revoca approvazione",
+            OrderTransition::Reject => "Rifiuta",
+            OrderTransition::Purchase => "Segna come acquistato",
+            OrderTransition::Ship => "Segna come spedito",
+            OrderTransition::ReceivePartially => "Segna come ricevuto in parte",
+            OrderTransition::Receive => "Segna come ricevuto",
+            OrderTransition::Close => "Chiudi",
+            OrderTransition::Cancel => "Annulla",
+            OrderTransition::Reopen => "Riapri",
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Price and stock of a part seen at a live distributor search.
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct PriceObservation {
+    pub distributor: String,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub distributor_pn: String,
+    pub quantity: i32,
+    pub unit_price: f64,
+    pub availability: i32,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl PriceObservation {
+    pub fn get_date(&self) -> String {
+        self.observed_at.format("%d/%m/%Y %H:%M").to_string()
+    }
+}
+
+/// First and latest price seen on a distributor for the same requested quantity, for the summary of the history page.
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceTrend {
+    pub distributor: String,
+    pub quantity: i32,
+    pub first_price: f64,
+    pub first_seen: String,
+    pub latest_price: f64,
+    pub latest_seen: String,
+    pub change_percent: f64,
+    pub observations: usize,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PartQuery {
+    pub manufacturer: Option<String>,
+    pub manufacturer_pn: String,
+}
+
+/// Answer of the price history JSON endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceHistory {
+    pub manufacturer: Option<String>,
+    pub manufacturer_pn: String,
+    pub trends: Vec<PriceTrend>,
+    pub observations: Vec<PriceObservation>,
+}
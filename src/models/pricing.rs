@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use crate::models::distributor_models::DistributorKind;
+
+/// Currency, locale and VAT used both to query the distributors and to price the BOM workbooks.
+#[derive(Debug, Clone)]
+pub struct PricingSettings {
+    pub currency: String, // ISO code, e.g. EUR
+    pub language: String,
+    pub country: String, // ISO code of the shipping country, e.g. IT
+    pub vat_rate: f64, // percentage, e.g. 22
+    pub vat_exempt: bool, // university purchases can be VAT exempt
+    handling_fees: HashMap<DistributorKind, f64>,
+}
+
+impl PricingSettings {
+    /// Reads `PRICING_CURRENCY`, `PRICING_LANGUAGE`, `PRICING_COUNTRY`, `VAT_RATE`, `VAT_EXEMPT` and the
+    /// `<DISTRIBUTOR>_HANDLING_FEE` of each distributor (e.g. `MOUSER_HANDLING_FEE`), defaulting to EUR, en, IT, 22%, not exempt
+    /// and no handling fee. The distributor specific variables, like `DIGIKEY_LOCALE_CURRENCY`, still take precedence in the requests.
+    pub fn from_env() -> PricingSettings {
+        let kinds = [
+            DistributorKind::Mouser,
+            DistributorKind::DigiKey,
+            DistributorKind::Farnell,
+            DistributorKind::Lcsc,
+            DistributorKind::Tme,
+        ];
+        let handling_fees = kinds
+            .iter()
+            .map(|kind| {
+                let fee = std::env::var(format!("{}_HANDLING_FEE", kind.name().to_uppercase()))
+                    .ok()
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .unwrap_or(0.0);
+                (*kind, fee)
+            })
+            .collect();
+        PricingSettings {
+            currency: std::env::var("PRICING_CURRENCY").unwrap_or("EUR".to_string()).to_uppercase(),
+            language: std::env::var("PRICING_LANGUAGE").unwrap_or("en".to_string()),
+            country: std::env::var("PRICING_COUNTRY").unwrap_or("IT".to_string()).to_uppercase(),
+            vat_rate: std::env::var("VAT_RATE")
+                .ok()
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(22.0),
+            vat_exempt: std::env::var("VAT_EXEMPT")
+                .map(|value| matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            handling_fees,
+        }
+    }
+
+    /// Factor turning a net price into the price paid, 1 when the purchase is VAT exempt.
+    pub fn vat_multiplier(&self) -> f64 {
+        match self.vat_exempt {
+            true => 1.0,
+            false => 1.0 + self.vat_rate / 100.0,
+        }
+    }
+
+    /// Header of the column with the prices paid.
+    pub fn vat_label(&self) -> String {
+        match self.vat_exempt {
+            true => "Price (VAT exempt)".to_string(),
+            false => format!("Price incl. VAT ({}%)", self.vat_rate),
+        }
+    }
+
+    pub fn handling_fee(&self, kind: DistributorKind) -> f64 {
+        self.handling_fees.get(&kind).copied().unwrap_or(0.0)
+    }
+
+    /// Spreadsheet number format showing amounts in the configured currency.
+    pub fn currency_format(&self) -> String {
+        let symbol = match self.currency.as_str() {
+            "EUR" => "€",
+            "USD" => "$",
+            "GBP" => "£",
+            "CHF" => "CHF",
+            other => other,
+        };
+        format!("#,##0.00 \"{}\"", symbol)
+    }
+}
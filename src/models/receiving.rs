@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::types::time::Date;
+use time::format_description;
+
+/// A delivery of an order item, recorded by a board member.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct ItemReceipt {
+    pub id: i32,
+    pub order_id: i32,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub quantity: i32,
+    pub received_on: Date,
+    pub received_by: String, // username
+    pub created_at: DateTime<Utc>,
+}
+
+impl ItemReceipt {
+    pub fn get_date(&self) -> String {
+        let format = format_description::parse("[day]/[month]/[year]").unwrap();
+        self.received_on.format(&format).unwrap_or("".to_string())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ItemReceiptForm {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub quantity: i32,
+    pub received_on: String, // yyyy-mm-dd from the date input, today when empty
+}
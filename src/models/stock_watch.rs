@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// A watch on an order item that could not be bought, with the quantity the item currently requires.
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct StockWatch {
+    pub id: i32,
+    pub order_id: i32,
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub quantity: i32,
+    pub author_id: i32,
+    pub order_description: String,
+    pub created_at: DateTime<Utc>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub notified_at: Option<DateTime<Utc>>,
+}
+
+impl StockWatch {
+    pub fn get_status(&self) -> String {
+        match (self.notified_at, self.last_checked_at) {
+            (Some(notified_at), _) => format!("Back in stock since {}", notified_at.format("%d/%m/%Y %H:%M")),
+            (None, Some(checked_at)) => format!("Still unavailable at {}", checked_at.format("%d/%m/%Y %H:%M")),
+            (None, None) => "Not checked yet".to_string(),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub message: String,
+    pub link: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Notification {
+    pub fn get_date(&self) -> String {
+        self.created_at.format("%d/%m/%Y %H:%M").to_string()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StockWatchForm {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+}
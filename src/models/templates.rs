@@ -1,5 +1,5 @@
 use askama::Template;
-use crate::{data::order::Order,models::{item::OrderItem, user_info::UserInfo}};
+use crate::{data::order::Order,models::{budget::{BudgetOverrun, BudgetOverview}, distributor_models::CostSummary, inventory::{InventoryItem, InventoryMovement, InventoryStock}, item::{CustomOrderItem, OrderItem, SourcingOverviewRow}, price_history::{PriceObservation, PriceTrend}, receiving::ItemReceipt, stock_watch::{Notification, StockWatch}, user_info::UserInfo}};
 
 #[derive(Template)]
 #[template(path = "pages/new_order.html")]
@@ -20,6 +20,7 @@ pub struct LoginPageTemplate {
 #[template(path = "pages/advisors_homepage.html")]
 pub struct AdvisorHomepageTemplate {
     pub orders: Vec<Order>,
+    pub notifications: Vec<Notification>,
 }
 
 #[derive(Template)]
@@ -31,18 +32,27 @@ pub struct EditOrderTemplate {
     pub sub_areas: Vec<String>,
     pub proposals: Vec<String>,
     pub projects: Vec<String>,
+    pub cost_summary: Option<CostSummary>,
+    pub sourcing: Vec<SourcingOverviewRow>,
+    pub stock_watches: Vec<StockWatch>,
+    pub custom_items: Vec<CustomOrderItem>,
+    pub receipts: Vec<ItemReceipt>,
+    pub inventory_stock: Vec<InventoryStock>,
 }
 
 #[derive(Template)]
 #[template(path = "pages/board_homepage.html")]
 pub struct BoardHomepageTemplate {
     pub orders: Vec<Order>,
+    pub budgets: BudgetOverview,
+    pub warning: Option<String>, // e.g. the notification of the last approval failed
 }
 
 #[derive(Template)]
 #[template(path = "pages/prof_homepage.html")]
 pub struct ProfHomepageTemplate {
     pub orders: Vec<Order>,
+    pub budgets: BudgetOverview,
 }
 
 #[derive(Template)]
@@ -51,6 +61,13 @@ pub struct CoffeePageTemplate {
     pub order_id: i32,
 }
 
+#[derive(Template)]
+#[template(path = "pages/approve_order.html")]
+pub struct ApproveOrderTemplate {
+    pub order_id: i32,
+    pub overruns: Vec<BudgetOverrun>,
+}
+
 #[derive(Template)]
 #[template(path = "pages/order_arithmetic.html")]
 pub struct OrderArithmeticPageTemplate {
@@ -67,4 +84,22 @@ pub struct ResetPasswordPageTemplate {
 #[template(path = "pages/user_settings.html")]
 pub struct UserSettingsPageTemplate {
     pub user_info: UserInfo,
-}
\ No newline at end of file
+}
+
+#[derive(Template)]
+#[template(path = "pages/price_history.html")]
+pub struct PriceHistoryTemplate {
+    pub manufacturer: String,
+    pub manufacturer_pn: String,
+    pub trends: Vec<PriceTrend>,
+    pub observations: Vec<PriceObservation>,
+}
+
+#[derive(Template)]
+#[template(path = "pages/inventory.html")]
+pub struct InventoryTemplate {
+    pub search: String,
+    pub items: Vec<InventoryItem>,
+    pub movements: Vec<InventoryMovement>,
+    pub default_location: String,
+}
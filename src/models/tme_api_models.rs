@@ -0,0 +1,48 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct TmeResponse<T> {
+    pub status: String,
+    pub data: Option<T>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchData {
+    pub product_list: Vec<Product>,
+    pub amount: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct Product {
+    pub symbol: String,
+    pub original_symbol: Option<String>,
+    pub producer: Option<String>,
+    pub description: Option<String>,
+    pub min_amount: Option<u32>,
+    pub product_information_page: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PricesAndStocksData {
+    pub currency: String,
+    pub product_list: Vec<ProductPrices>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProductPrices {
+    pub symbol: String,
+    pub price_list: Vec<PriceBreak>,
+    pub amount: u32, // quantity in stock
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PriceBreak {
+    pub amount: u32,
+    pub price_value: f64,
+}
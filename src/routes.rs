@@ -1,5 +1,5 @@
 use axum::{middleware, routing::{get, post}, Router};
-use crate::handlers::{advisors_homepage, auth, board_homepage, edit_order, new_order, order_operations, password_reset, prof_homepage, user_settings};
+use crate::handlers::{advisors_homepage, auth, board_homepage, budget, edit_order, inventory, new_order, order_operations, password_reset, price_history, prof_homepage, user_settings};
 use crate::models::app;
 use tower_http::services::ServeDir;
 use crate::middlewares;
@@ -13,6 +13,9 @@ pub fn get_router(app_state: app::AppState) -> Router {
     .merge(home_routes())
     .merge(orders_routes())
     .merge(settings_routes())
+    .merge(parts_routes())
+    .merge(budget_routes())
+    .merge(inventory_routes())
     .route("/reset-password", get(password_reset::reset_password_page))
     .route("/reset-password", post(password_reset::reset_password_submit))
     .route("/request-pwd-reset", get(password_reset::request_password_reset))
@@ -32,6 +35,7 @@ fn home_routes() -> Router<app::AppState> {
         .route("/board/home", get(board_homepage::board_homepage_handler))
         .route("/prof", get(prof_homepage::prof_homepage_handler))
         .route("/board/users", get(board_homepage::board_manage_users))
+        .route("/notifications/:id/read", post(advisors_homepage::mark_notification_read_handler))
         .route_layer(middleware::from_fn(middlewares::auth::required_authentication))
 }
 
@@ -41,6 +45,29 @@ fn settings_routes() -> Router<app::AppState> {
         .route("/settings/set-email", post(user_settings::update_email))
 }
 
+fn parts_routes() -> Router<app::AppState> {
+    Router::new()
+        .route("/parts/history", get(price_history::price_history_page_handler))
+        .route("/parts/history/json", get(price_history::price_history_json_handler))
+        .route_layer(middleware::from_fn(middlewares::auth::required_authentication))
+}
+
+fn budget_routes() -> Router<app::AppState> {
+    Router::new()
+        .route("/budgets", get(budget::budgets_json_handler))
+        .route("/budgets/set", post(budget::set_budget_handler))
+        .route_layer(middleware::from_fn(middlewares::auth::required_authentication))
+}
+
+fn inventory_routes() -> Router<app::AppState> {
+    Router::new()
+        .route("/inventory", get(inventory::inventory_page_handler))
+        .route("/inventory/json", get(inventory::inventory_json_handler))
+        .route("/inventory/adjust", post(inventory::adjust_inventory_handler))
+        .route("/inventory/adjust/json", post(inventory::adjust_inventory_json_handler))
+        .route_layer(middleware::from_fn(middlewares::auth::required_authentication))
+}
+
 fn orders_routes() -> Router<app::AppState> {
     Router::new()
         .route("/orders/list", get(order_operations::list_orders_handler))
@@ -60,13 +87,34 @@ fn edit_order_routes() -> Router<app::AppState> {
         .route("/orders/:id/edit/submit", post(edit_order::submit_order_handler))
         .route("/orders/:id/edit/bulk-add", post(edit_order::bulk_add_handler))
         .route("/orders/:id/edit/generate-bom", post(edit_order::generate_bom_handler))
+        .route("/orders/:id/edit/refresh-bom", post(edit_order::refresh_bom_handler))
         .route("/orders/:id/edit/download-bom", post(edit_order::download_bom_handler))
+        .route("/orders/:id/edit/cost-summary", get(edit_order::get_cost_summary_handler))
+        .route("/orders/:id/edit/custom-items", post(edit_order::add_custom_item_handler))
+        .route("/orders/:id/edit/custom-items/:item_id/delete", post(edit_order::delete_custom_item_handler))
+        .route("/orders/:id/edit/watch", post(edit_order::add_stock_watch_handler))
+        .route("/orders/:id/edit/watch/:watch_id/delete", post(edit_order::delete_stock_watch_handler))
+        .route("/orders/:id/edit/watch/:watch_id/check", post(edit_order::check_stock_watch_handler))
+        .route("/orders/:id/edit/receipts", post(edit_order::add_item_receipt_handler))
         .route("/orders/:id/edit/create-mouser-cart", post(edit_order::download_mouser_cart_handler))
         .route("/orders/:id/edit/download-digikey-cart", post(edit_order::download_digikey_cart_handler))
-        .route("/orders/:id/ready", post(edit_order::mark_order_ready_handler))
-        .route("/orders/:id/unready", post(edit_order::mark_order_unready_handler))
-        .route("/orders/:id/confirm", post(edit_order::mark_order_confirmed_handler))
-        .route("/orders/:id/unconfirm", post(edit_order::mark_order_unconfirmed_handler))
+        .route("/orders/:id/edit/download-farnell-cart", post(edit_order::download_farnell_cart_handler))
+        .route("/orders/:id/edit/download-lcsc-cart", post(edit_order::download_lcsc_cart_handler))
+        .route("/orders/:id/edit/download-tme-cart", post(edit_order::download_tme_cart_handler))
+        .route("/orders/:id/edit/download-mouser-bom-csv", post(edit_order::download_mouser_bom_csv_handler))
+        .route("/orders/:id/edit/download-digikey-bom-csv", post(edit_order::download_digikey_bom_csv_handler))
+        .route("/orders/:id/submit", post(edit_order::submit_order_for_approval_handler))
+        .route("/orders/:id/withdraw", post(edit_order::withdraw_order_handler))
+        .route("/orders/:id/approve", post(edit_order::approve_order_handler))
+        .route("/orders/:id/unapprove", post(edit_order::unapprove_order_handler))
+        .route("/orders/:id/reject", post(edit_order::reject_order_handler))
+        .route("/orders/:id/purchase", post(edit_order::purchase_order_handler))
+        .route("/orders/:id/ship", post(edit_order::ship_order_handler))
+        .route("/orders/:id/receive-partially", post(edit_order::receive_order_partially_handler))
+        .route("/orders/:id/receive", post(edit_order::receive_order_handler))
+        .route("/orders/:id/close", post(edit_order::close_order_handler))
+        .route("/orders/:id/cancel", post(edit_order::cancel_order_handler))
+        .route("/orders/:id/reopen", post(edit_order::reopen_order_handler))
         .route("/orders/:id/delete", post(edit_order::delete_order_handler))
 }
 
@@ -4,7 +4,7 @@ function showLoadingContainer() {
 
 let itemIndex = 0;
 
-function addItemEntry(proposal = "", project = "", manufacturer = "", manufacturerPn = "", quantity = 1) {
+function addItemEntry(proposal = "", project = "", manufacturer = "", manufacturerPn = "", quantity = 1, preferredDistributor = "", pinnedDistributorPn = "") {
     const container = document.getElementById('items-container');
 
     const div = document.createElement('div');
@@ -40,6 +40,18 @@ function addItemEntry(proposal = "", project = "", manufacturer = "", manufactur
     quantityInput.value = quantity;
     quantityInput.required = true;
 
+    // optional: buy the item from this distributor only, possibly with an exact distributor P.N.
+    const distributorSelect = document.createElement('select');
+    distributorSelect.name = `items_preferred_distributor_${itemIndex}`;
+    distributorSelect.innerHTML = document.getElementById('distributor-template').innerHTML;
+    distributorSelect.value = preferredDistributor;
+
+    const pinnedPnInput = document.createElement('input');
+    pinnedPnInput.type = 'text';
+    pinnedPnInput.value = pinnedDistributorPn;
+    pinnedPnInput.name = `items_pinned_distributor_pn_${itemIndex}`;
+    pinnedPnInput.placeholder = 'Distributor P.N.';
+
     const deleteButton = document.createElement('button');
     deleteButton.type = 'button';
     deleteButton.className = 'delete-button';
@@ -51,6 +63,8 @@ function addItemEntry(proposal = "", project = "", manufacturer = "", manufactur
     div.appendChild(manufacturerInput);
     div.appendChild(manufacturerPnInput);
     div.appendChild(quantityInput);
+    div.appendChild(distributorSelect);
+    div.appendChild(pinnedPnInput);
     div.appendChild(deleteButton);
 
     container.appendChild(div);